@@ -0,0 +1,53 @@
+//! Compare `FCMRequestBuilder::data_map` with `FCMRequestBuilder::data` on
+//! a 50-key map, the maps handed to `data_map` being built beforehand.
+//!
+//! Run with `cargo bench --bench data_map`.
+
+use fcm_http1::FCMRequestBuilder;
+use serde_json::{Map, Value};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const KEYS: usize = 50;
+const BATCH: usize = 1_000;
+const BATCHES: u32 = 100;
+
+fn map() -> Map<String, Value> {
+    (0..KEYS)
+        .map(|i| (format!("key-{}", i), Value::from(format!("value-{}", i))))
+        .collect()
+}
+
+/// The mean time of `routine` over the maps of `BATCHES` batches, the maps of
+/// a batch being built before timing it.
+fn bench(routine: impl Fn(Map<String, Value>)) -> Duration {
+    let map = map();
+    let mut elapsed = Duration::ZERO;
+
+    for _ in 0..BATCHES {
+        let maps: Vec<_> = (0..BATCH).map(|_| map.clone()).collect();
+
+        let start = Instant::now();
+        maps.into_iter().for_each(&routine);
+        elapsed += start.elapsed();
+    }
+
+    elapsed / (BATCHES * BATCH as u32)
+}
+
+fn main() {
+    let data = bench(|map| {
+        let mut builder = FCMRequestBuilder::to_token("api_key", "my-project", "token", None);
+        builder.data(black_box(&map)).unwrap();
+        black_box(builder);
+    });
+
+    let data_map = bench(|map| {
+        let mut builder = FCMRequestBuilder::to_token("api_key", "my-project", "token", None);
+        builder.data_map(black_box(map)).unwrap();
+        black_box(builder);
+    });
+
+    println!("data      {:>10?} per message", data);
+    println!("data_map  {:>10?} per message", data_map);
+}
@@ -26,7 +26,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         ap.refer(&mut project)
             .add_option(&["-p", "--project"], Store, "Project");
         ap.refer(&mut validate_only)
-        .add_option(&["-d", "--validate-only"], Store, "Validate only (dry run)");
+            .add_option(&["-d", "--validate-only"], Store, "Validate only (dry run)");
         ap.parse_args_or_exit();
     }
 
@@ -38,7 +38,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         _ => false,
     };
 
-    let mut builder = FCMRequestBuilder::new(&api_key, &project, &device_token, Some(validate_only_bool));
+    let mut builder = FCMRequestBuilder::to_token(&api_key, &project, &device_token, Some(validate_only_bool));
     builder.data(&data)?;
 
     let response = client.send(builder.finalize()).await?;
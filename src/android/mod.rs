@@ -0,0 +1,831 @@
+use crate::message::{check_data_key, string_data, BuildError, FcmOptions, Priority};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(test)]
+mod tests;
+
+/// The longest time FCM keeps a message for an offline device, 4 weeks.
+pub const MAX_TTL: Duration = Duration::from_secs(2_419_200);
+
+/// The Android specific options of a message. Use the corresponding
+/// `AndroidConfigBuilder` to get an instance, and set it with
+/// `FCMRequestBuilder::android`.
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct AndroidConfig<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) collapse_key: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    direct_boot_ok: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fcm_options: Option<FcmOptions<'a>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) notification: Option<AndroidNotification<'a>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) priority: Option<Priority>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) restricted_package_name: Option<&'a str>,
+
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::wire_time::serialize_optional_duration"
+    )]
+    pub(crate) ttl: Option<Duration>,
+}
+
+impl<'a> AndroidConfig<'a> {
+    /// The key identifying the group of messages that can be collapsed.
+    pub fn collapse_key(&self) -> Option<&'a str> {
+        self.collapse_key
+    }
+
+    /// The data sent to Android devices instead of the data of the message.
+    pub fn data(&self) -> Option<&BTreeMap<String, String>> {
+        self.data.as_ref()
+    }
+
+    /// Whether the message can be delivered before the device is unlocked.
+    pub fn direct_boot_ok(&self) -> Option<bool> {
+        self.direct_boot_ok
+    }
+
+    /// The label of the message in the FCM analytics.
+    pub fn analytics_label(&self) -> Option<&'a str> {
+        self.fcm_options.and_then(|fcm_options| fcm_options.analytics_label)
+    }
+
+    /// The FCM options of the message on Android.
+    pub fn fcm_options(&self) -> Option<&FcmOptions<'a>> {
+        self.fcm_options.as_ref()
+    }
+
+    /// The Android specific options of the notification of the message.
+    pub fn notification(&self) -> Option<&AndroidNotification<'a>> {
+        self.notification.as_ref()
+    }
+
+    /// The string fields `FCMRequestBuilder::empty_strings` applies to, by
+    /// path in the message.
+    pub(crate) fn string_fields(&mut self) -> Vec<(&'static str, &mut Option<&'a str>)> {
+        let mut fields = vec![
+            ("android.collapse_key", &mut self.collapse_key),
+            ("android.restricted_package_name", &mut self.restricted_package_name),
+        ];
+
+        if let Some(ref mut fcm_options) = self.fcm_options {
+            fields.push(("android.fcm_options.analytics_label", &mut fcm_options.analytics_label));
+        }
+
+        if let Some(ref mut notification) = self.notification {
+            fields.extend(notification.string_fields());
+        }
+
+        fields
+    }
+
+    /// The delivery priority of the message.
+    pub fn priority(&self) -> Option<Priority> {
+        self.priority
+    }
+
+    /// The package name of the application the registration tokens must
+    /// match.
+    pub fn restricted_package_name(&self) -> Option<&'a str> {
+        self.restricted_package_name
+    }
+
+    /// How long FCM keeps the message if the device is offline.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
+
+/// A builder to get an `AndroidConfig` instance.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{AndroidConfigBuilder, FCMRequestBuilder};
+/// use std::time::Duration;
+///
+/// let mut builder = AndroidConfigBuilder::new();
+/// builder.ttl(Duration::from_secs(3600)).collapse_key("scores");
+/// let android = builder.finalize();
+///
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+/// builder.android(android);
+/// let message = builder.finalize();
+/// ```
+#[derive(Default)]
+pub struct AndroidConfigBuilder<'a> {
+    config: AndroidConfig<'a>,
+}
+
+impl<'a> AndroidConfigBuilder<'a> {
+    /// Get a new `AndroidConfigBuilder` instance, with no options set.
+    pub fn new() -> AndroidConfigBuilder<'a> {
+        Self::default()
+    }
+
+    /// Set the key identifying a group of messages that can be collapsed, so
+    /// only the last one is delivered when the device comes back online.
+    pub fn collapse_key(&mut self, collapse_key: &'a str) -> &mut Self {
+        self.config.collapse_key = Some(collapse_key);
+        self
+    }
+
+    /// Set the data sent to Android devices, replacing the data of the
+    /// message on Android only. Like `FCMRequestBuilder::data`, it can be
+    /// anything that Serde can serialize to a JSON map whose keys are not
+    /// reserved, and the values which are not strings are converted to their
+    /// JSON text.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// use fcm_http1::AndroidConfigBuilder;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("link", "app://matches/42");
+    ///
+    /// let mut builder = AndroidConfigBuilder::new();
+    /// builder.data(&map).unwrap();
+    /// let android = builder.finalize();
+    /// ```
+    pub fn data(&mut self, data: &dyn erased_serde::Serialize) -> Result<&mut Self, BuildError> {
+        self.config.data = Some(string_data("android.data", data)?.collect());
+        Ok(self)
+    }
+
+    /// Add a key-value pair to the data sent to Android devices, see
+    /// `data`. Can be called several times. Fails if the key is reserved by
+    /// FCM, like `data`.
+    pub fn data_entry(&mut self, key: &str, value: &str) -> Result<&mut Self, BuildError> {
+        check_data_key("android.data", key)?;
+
+        self.config
+            .data
+            .get_or_insert_with(BTreeMap::new)
+            .insert(key.to_string(), value.to_string());
+        Ok(self)
+    }
+
+    /// Set to `true` to deliver the message even before the device is
+    /// unlocked, to the apps which support direct boot.
+    pub fn direct_boot_ok(&mut self, direct_boot_ok: bool) -> &mut Self {
+        self.config.direct_boot_ok = Some(direct_boot_ok);
+        self
+    }
+
+    /// Set the FCM options of the message on Android, replacing the ones
+    /// previously set.
+    pub fn fcm_options(&mut self, fcm_options: FcmOptions<'a>) -> &mut Self {
+        self.config.fcm_options = Some(fcm_options);
+        self
+    }
+
+    /// Set the label of the message in the FCM analytics, as
+    /// `android.fcm_options.analytics_label`. A shortcut for `fcm_options`.
+    pub fn analytics_label(&mut self, analytics_label: &'a str) -> &mut Self {
+        self.config.fcm_options = Some(FcmOptions {
+            analytics_label: Some(analytics_label),
+        });
+        self
+    }
+
+    /// Set the Android specific options of the notification of the message.
+    pub fn notification(&mut self, notification: AndroidNotification<'a>) -> &mut Self {
+        self.config.notification = Some(notification);
+        self
+    }
+
+    /// Set the delivery priority of the message. This is not how prominently
+    /// the notification is shown, set with
+    /// `AndroidNotificationBuilder::notification_priority`.
+    pub fn priority(&mut self, priority: Priority) -> &mut Self {
+        self.config.priority = Some(priority);
+        self
+    }
+
+    /// Set the package name of the application the registration tokens must
+    /// match.
+    pub fn restricted_package_name(&mut self, restricted_package_name: &'a str) -> &mut Self {
+        self.config.restricted_package_name = Some(restricted_package_name);
+        self
+    }
+
+    /// How long FCM keeps the message if the device is offline. Durations
+    /// longer than `MAX_TTL`, the maximum and default, are clamped to it.
+    pub fn ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.ttl = Some(ttl.min(MAX_TTL));
+        self
+    }
+
+    /// Complete the build and get an `AndroidConfig` instance
+    pub fn finalize(self) -> AndroidConfig<'a> {
+        self.config
+    }
+}
+
+/// The Android specific options of a notification, in
+/// `android.notification`. Use the corresponding `AndroidNotificationBuilder`
+/// to get an instance, and set it with `AndroidConfigBuilder::notification`.
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct AndroidNotification<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_loc_args: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_loc_key: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel_id: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    click_action: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_light_settings: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_sound: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_vibrate_timings: Option<bool>,
+
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::wire_time::serialize_optional_timestamp_nanos"
+    )]
+    event_time: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light_settings: Option<LightSettings>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_only: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notification_count: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notification_priority: Option<NotificationPriority>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sound: Option<Cow<'a, str>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sticky: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticker: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title_loc_args: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title_loc_key: Option<&'a str>,
+
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::wire_time::serialize_optional_durations"
+    )]
+    vibrate_timings: Option<Vec<Duration>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<Visibility>,
+}
+
+/// A color, with its channels between 0 and 1.
+#[derive(Serialize, PartialEq, Debug, Clone, Copy)]
+pub struct Color {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+}
+
+impl Color {
+    /// Parse a color in `#rrggbb` or `#rrggbbaa` format, in any case. The
+    /// alpha is 1 when not given.
+    pub fn from_hex(hex: &str) -> Result<Color, BuildError> {
+        let invalid = || BuildError::validation("color", format!("`{}` is not in #rrggbb or #rrggbbaa format", hex));
+
+        let digits = hex.strip_prefix('#').ok_or_else(invalid)?;
+
+        if !matches!(digits.len(), 6 | 8) || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let channel = |i: usize| match digits.get(i..i + 2) {
+            Some(channel) => f32::from(u8::from_str_radix(channel, 16).unwrap()) / 255.0,
+            None => 1.0,
+        };
+
+        Ok(Color {
+            red: channel(0),
+            green: channel(2),
+            blue: channel(4),
+            alpha: channel(6),
+        })
+    }
+
+    /// Format the color as `#rrggbb`, or `#rrggbbaa` when it is not opaque.
+    /// The channels are rounded to the nearest of the 256 values.
+    pub fn to_hex(&self) -> String {
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        let hex = format!(
+            "#{:02x}{:02x}{:02x}",
+            channel(self.red),
+            channel(self.green),
+            channel(self.blue)
+        );
+
+        match channel(self.alpha) {
+            255 => hex,
+            alpha => format!("{}{:02x}", hex, alpha),
+        }
+    }
+}
+
+/// The blinking of the LED of the device for a notification.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct LightSettings {
+    color: Color,
+
+    #[serde(serialize_with = "crate::wire_time::serialize_duration")]
+    light_off_duration: Duration,
+
+    #[serde(serialize_with = "crate::wire_time::serialize_duration")]
+    light_on_duration: Duration,
+}
+
+impl LightSettings {
+    /// The LED blinks in `color`, on for `light_on_duration` then off for
+    /// `light_off_duration`.
+    pub fn new(color: Color, light_on_duration: Duration, light_off_duration: Duration) -> LightSettings {
+        LightSettings {
+            color,
+            light_off_duration,
+            light_on_duration,
+        }
+    }
+
+    /// The color of the LED.
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// How long the LED is on when blinking.
+    pub fn light_on_duration(&self) -> Duration {
+        self.light_on_duration
+    }
+
+    /// How long the LED is off when blinking.
+    pub fn light_off_duration(&self) -> Duration {
+        self.light_off_duration
+    }
+}
+
+/// The sound Android plays for a notification.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Sound<'a> {
+    /// The default sound of the device, set as `default_sound`.
+    Default,
+
+    /// A sound bundled in the app, set as `sound`: the name of a file in
+    /// its `res/raw/` directory, without the extension.
+    Custom(Cow<'a, str>),
+}
+
+/// How prominently Android shows a notification. Not to be confused with
+/// the delivery `Priority` of the message.
+#[derive(Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum NotificationPriority {
+    #[serde(rename = "PRIORITY_MIN")]
+    Min,
+    #[serde(rename = "PRIORITY_LOW")]
+    Low,
+    #[serde(rename = "PRIORITY_DEFAULT")]
+    Default,
+    #[serde(rename = "PRIORITY_HIGH")]
+    High,
+    #[serde(rename = "PRIORITY_MAX")]
+    Max,
+}
+
+/// How much of a notification Android shows on the lock screen.
+#[derive(Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Visibility {
+    /// Show the notification, hiding its content.
+    Private,
+
+    /// Show the whole notification.
+    Public,
+
+    /// Do not show the notification.
+    Secret,
+}
+
+impl<'a> AndroidNotification<'a> {
+    /// The key of the string resource of the body, for localization.
+    pub fn body_loc_key(&self) -> Option<&'a str> {
+        self.body_loc_key
+    }
+
+    /// The values replacing the format specifiers of the body string.
+    pub fn body_loc_args(&self) -> Option<&[Cow<'a, str>]> {
+        self.body_loc_args.as_deref()
+    }
+
+    /// The id of the notification channel the notification is shown in.
+    pub fn channel_id(&self) -> Option<&'a str> {
+        self.channel_id
+    }
+
+    /// The action of the intent filter opened when the user clicks on the
+    /// notification.
+    pub fn click_action(&self) -> Option<&'a str> {
+        self.click_action
+    }
+
+    /// The color of the icon, in #rrggbb format.
+    pub fn color(&self) -> Option<&'a str> {
+        self.color
+    }
+
+    /// When the event the notification is about happened.
+    pub fn event_time(&self) -> Option<DateTime<Utc>> {
+        self.event_time
+    }
+
+    /// Whether the LED of the device blinks with its default settings.
+    pub fn default_light_settings(&self) -> Option<bool> {
+        self.default_light_settings
+    }
+
+    /// Whether the device vibrates with its default pattern.
+    pub fn default_vibrate_timings(&self) -> Option<bool> {
+        self.default_vibrate_timings
+    }
+
+    /// Whether the default sound of the device is played.
+    pub fn default_sound(&self) -> Option<bool> {
+        self.default_sound
+    }
+
+    /// The drawable resource of the notification icon.
+    pub fn icon(&self) -> Option<&'a str> {
+        self.icon
+    }
+
+    /// The URL of the big picture shown in the notification.
+    pub fn image(&self) -> Option<&'a str> {
+        self.image
+    }
+
+    /// How the LED of the device blinks.
+    pub fn light_settings(&self) -> Option<&LightSettings> {
+        self.light_settings.as_ref()
+    }
+
+    /// Whether the notification is only shown on the device, and not
+    /// bridged to other devices such as a watch.
+    pub fn local_only(&self) -> Option<bool> {
+        self.local_only
+    }
+
+    /// The number of items the notification stands for, shown on the
+    /// launcher badge.
+    pub fn notification_count(&self) -> Option<i32> {
+        self.notification_count
+    }
+
+    /// How prominently the notification is shown.
+    pub fn notification_priority(&self) -> Option<NotificationPriority> {
+        self.notification_priority
+    }
+
+    /// The sound bundled in the app played for the notification.
+    pub fn sound(&self) -> Option<&str> {
+        self.sound.as_deref()
+    }
+
+    /// Whether the notification stays when the user clicks on it.
+    pub fn sticky(&self) -> Option<bool> {
+        self.sticky
+    }
+
+    /// The tag used to replace existing notifications.
+    pub fn tag(&self) -> Option<&'a str> {
+        self.tag
+    }
+
+    /// The text read by the accessibility services.
+    pub fn ticker(&self) -> Option<&'a str> {
+        self.ticker
+    }
+
+    /// The key of the string resource of the title, for localization.
+    pub fn title_loc_key(&self) -> Option<&'a str> {
+        self.title_loc_key
+    }
+
+    /// The values replacing the format specifiers of the title string.
+    pub fn title_loc_args(&self) -> Option<&[Cow<'a, str>]> {
+        self.title_loc_args.as_deref()
+    }
+
+    /// The vibration pattern, alternating how long the vibrator is off and
+    /// on.
+    pub fn vibrate_timings(&self) -> Option<&[Duration]> {
+        self.vibrate_timings.as_deref()
+    }
+
+    /// How much of the notification is shown on the lock screen.
+    pub fn visibility(&self) -> Option<Visibility> {
+        self.visibility
+    }
+
+    /// The string fields `FCMRequestBuilder::empty_strings` applies to, by
+    /// path in the message.
+    fn string_fields(&mut self) -> Vec<(&'static str, &mut Option<&'a str>)> {
+        vec![
+            ("android.notification.body_loc_key", &mut self.body_loc_key),
+            ("android.notification.channel_id", &mut self.channel_id),
+            ("android.notification.click_action", &mut self.click_action),
+            ("android.notification.color", &mut self.color),
+            ("android.notification.icon", &mut self.icon),
+            ("android.notification.image", &mut self.image),
+            ("android.notification.tag", &mut self.tag),
+            ("android.notification.ticker", &mut self.ticker),
+            ("android.notification.title_loc_key", &mut self.title_loc_key),
+        ]
+    }
+}
+
+/// The localization args to serialize, none when `args` is empty.
+fn loc_args<'a, S>(args: &'a [S]) -> Option<Vec<Cow<'a, str>>>
+where
+    S: AsRef<str>,
+{
+    if args.is_empty() {
+        return None;
+    }
+
+    Some(args.iter().map(|arg| Cow::Borrowed(arg.as_ref())).collect())
+}
+
+/// Check `color` is in #rrggbb format, the only one FCM accepts.
+pub(crate) fn is_valid_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// A builder to get an `AndroidNotification` instance.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{AndroidConfigBuilder, AndroidNotificationBuilder};
+///
+/// let mut builder = AndroidNotificationBuilder::new();
+/// builder.icon("ic_goal").color("#1a73e8");
+///
+/// let mut android = AndroidConfigBuilder::new();
+/// android.notification(builder.finalize());
+/// ```
+#[derive(Default)]
+pub struct AndroidNotificationBuilder<'a> {
+    notification: AndroidNotification<'a>,
+}
+
+impl<'a> AndroidNotificationBuilder<'a> {
+    /// Get a new `AndroidNotificationBuilder` instance, with no options set.
+    pub fn new() -> AndroidNotificationBuilder<'a> {
+        Self::default()
+    }
+
+    /// Set the key of the string resource of the body in the app, to show
+    /// the body in the language of the user.
+    pub fn body_loc_key(&mut self, body_loc_key: &'a str) -> &mut Self {
+        self.notification.body_loc_key = Some(body_loc_key);
+        self
+    }
+
+    /// Set the values replacing the format specifiers of the body string.
+    /// Not serialized when empty.
+    pub fn body_loc_args<S>(&mut self, body_loc_args: &'a [S]) -> &mut Self
+    where
+        S: AsRef<str>,
+    {
+        self.notification.body_loc_args = loc_args(body_loc_args);
+        self
+    }
+
+    /// Set the id of the notification channel the notification is shown in,
+    /// required since Android 8. The app must have created the channel,
+    /// otherwise the one of the app manifest is used.
+    pub fn channel_id(&mut self, channel_id: &'a str) -> &mut Self {
+        self.notification.channel_id = Some(channel_id);
+        self
+    }
+
+    /// Set the action of the intent filter opened when the user clicks on
+    /// the notification.
+    pub fn click_action(&mut self, click_action: &'a str) -> &mut Self {
+        self.notification.click_action = Some(click_action);
+        self
+    }
+
+    /// Set the color of the icon, in #rrggbb format. The format is checked by
+    /// `FCMRequestBuilder::try_finalize`.
+    pub fn color(&mut self, color: &'a str) -> &mut Self {
+        self.notification.color = Some(color);
+        self
+    }
+
+    /// Set to `true` to blink the LED of the device with its default
+    /// settings.
+    pub fn default_light_settings(&mut self, default_light_settings: bool) -> &mut Self {
+        self.notification.default_light_settings = Some(default_light_settings);
+        self
+    }
+
+    /// Set to `true` to vibrate the device with its default pattern.
+    pub fn default_vibrate_timings(&mut self, default_vibrate_timings: bool) -> &mut Self {
+        self.notification.default_vibrate_timings = Some(default_vibrate_timings);
+        self
+    }
+
+    /// Set to `true` to play the default sound of the device. Cannot be
+    /// combined with a custom sound, which `FCMRequestBuilder::try_finalize`
+    /// checks.
+    pub fn default_sound(&mut self, default_sound: bool) -> &mut Self {
+        self.notification.default_sound = Some(default_sound);
+        self
+    }
+
+    /// Set when the event the notification is about happened, shown instead
+    /// of when the notification was received. Fails for times before
+    /// 1970-01-01T00:00:00Z, or too far in the future to be a date.
+    pub fn event_time(&mut self, event_time: SystemTime) -> Result<&mut Self, BuildError> {
+        let since_epoch = event_time.duration_since(UNIX_EPOCH).map_err(|e| {
+            BuildError::validation(
+                "android.notification.event_time",
+                format!("{:?} before 1970-01-01T00:00:00Z", e.duration()),
+            )
+        })?;
+
+        let secs = i64::try_from(since_epoch.as_secs()).ok();
+        let event_time = secs
+            .and_then(|secs| Utc.timestamp_opt(secs, since_epoch.subsec_nanos()).single())
+            .ok_or_else(|| {
+                BuildError::validation(
+                    "android.notification.event_time",
+                    format!("{:?} after 1970-01-01T00:00:00Z", since_epoch),
+                )
+            })?;
+
+        Ok(self.event_time_utc(event_time))
+    }
+
+    /// Same as `event_time`, from a `chrono` date.
+    pub fn event_time_utc(&mut self, event_time: DateTime<Utc>) -> &mut Self {
+        self.notification.event_time = Some(event_time);
+        self
+    }
+
+    /// Set the drawable resource of the notification icon, e.g. `ic_goal`.
+    pub fn icon(&mut self, icon: &'a str) -> &mut Self {
+        self.notification.icon = Some(icon);
+        self
+    }
+
+    /// Set the URL of the big picture shown in the notification. With the
+    /// `url` feature, `FCMRequestBuilder::try_finalize` checks it is an
+    /// http(s) URL.
+    pub fn image(&mut self, image: &'a str) -> &mut Self {
+        self.notification.image = Some(image);
+        self
+    }
+
+    /// Set how the LED of the device blinks.
+    pub fn light_settings(&mut self, light_settings: LightSettings) -> &mut Self {
+        self.notification.light_settings = Some(light_settings);
+        self
+    }
+
+    /// Set to `true` to only show the notification on the device, without
+    /// bridging it to other devices such as a watch.
+    pub fn local_only(&mut self, local_only: bool) -> &mut Self {
+        self.notification.local_only = Some(local_only);
+        self
+    }
+
+    /// Set the number of items the notification stands for, shown on the
+    /// launcher badge. An explicit `0` is sent, and clears the badge.
+    pub fn notification_count(&mut self, notification_count: i32) -> &mut Self {
+        self.notification.notification_count = Some(notification_count);
+        self
+    }
+
+    /// Set how prominently the notification is shown, e.g. whether it pops
+    /// up on the screen. This is not the delivery priority of the message,
+    /// set with `AndroidConfigBuilder::priority`, which controls how fast FCM
+    /// delivers it.
+    pub fn notification_priority(&mut self, notification_priority: NotificationPriority) -> &mut Self {
+        self.notification.notification_priority = Some(notification_priority);
+        self
+    }
+
+    /// Set the sound played for the notification, either the default one
+    /// of the device or one bundled in the app.
+    pub fn sound(&mut self, sound: Sound<'a>) -> &mut Self {
+        match sound {
+            Sound::Default => self.default_sound(true),
+            Sound::Custom(sound) => {
+                self.notification.sound = Some(sound);
+                self
+            }
+        }
+    }
+
+    /// Set to `true` for the notification to stay when the user clicks on
+    /// it. Set to `false`, it is sent as such.
+    pub fn sticky(&mut self, sticky: bool) -> &mut Self {
+        self.notification.sticky = Some(sticky);
+        self
+    }
+
+    /// Set the tag used to replace existing notifications.
+    pub fn tag(&mut self, tag: &'a str) -> &mut Self {
+        self.notification.tag = Some(tag);
+        self
+    }
+
+    /// Set the text read by the accessibility services.
+    pub fn ticker(&mut self, ticker: &'a str) -> &mut Self {
+        self.notification.ticker = Some(ticker);
+        self
+    }
+
+    /// Set the key of the string resource of the title in the app, to show
+    /// the title in the language of the user.
+    pub fn title_loc_key(&mut self, title_loc_key: &'a str) -> &mut Self {
+        self.notification.title_loc_key = Some(title_loc_key);
+        self
+    }
+
+    /// Set the values replacing the format specifiers of the title string.
+    /// Not serialized when empty.
+    pub fn title_loc_args<S>(&mut self, title_loc_args: &'a [S]) -> &mut Self
+    where
+        S: AsRef<str>,
+    {
+        self.notification.title_loc_args = loc_args(title_loc_args);
+        self
+    }
+
+    /// Set the vibration pattern, alternating how long the vibrator is off
+    /// and on, starting with off. Not serialized when empty.
+    pub fn vibrate_timings(&mut self, vibrate_timings: &[Duration]) -> &mut Self {
+        self.notification.vibrate_timings = Some(vibrate_timings.to_vec()).filter(|timings| !timings.is_empty());
+        self
+    }
+
+    /// Set how much of the notification is shown on the lock screen.
+    pub fn visibility(&mut self, visibility: Visibility) -> &mut Self {
+        self.notification.visibility = Some(visibility);
+        self
+    }
+
+    /// Complete the build and get an `AndroidNotification` instance
+    pub fn finalize(self) -> AndroidNotification<'a> {
+        self.notification
+    }
+}
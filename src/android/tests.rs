@@ -0,0 +1,436 @@
+use crate::android::{
+    AndroidConfigBuilder, AndroidNotificationBuilder, Color, LightSettings, NotificationPriority, Sound, Visibility,
+    MAX_TTL,
+};
+use crate::{BuildError, Priority};
+use serde_json::json;
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn serialized_ttl(ttl: Duration) -> serde_json::Value {
+    let mut builder = AndroidConfigBuilder::new();
+    builder.ttl(ttl);
+
+    serde_json::to_value(builder.finalize()).unwrap()["ttl"].clone()
+}
+
+#[test]
+fn should_not_serialize_an_unset_ttl() {
+    let android = AndroidConfigBuilder::new().finalize();
+
+    assert_eq!(json!({}), serde_json::to_value(android).unwrap());
+}
+
+#[test]
+fn should_serialize_whole_ttls_in_seconds() {
+    assert_eq!(json!("0s"), serialized_ttl(Duration::ZERO));
+    assert_eq!(json!("3600s"), serialized_ttl(Duration::from_secs(3600)));
+}
+
+#[test]
+fn should_serialize_fractional_ttls() {
+    assert_eq!(json!("1.500s"), serialized_ttl(Duration::from_millis(1500)));
+    assert_eq!(json!("0.000001s"), serialized_ttl(Duration::from_micros(1)));
+    assert_eq!(json!("1.000000001s"), serialized_ttl(Duration::new(1, 1)));
+}
+
+#[test]
+fn should_clamp_the_ttl_to_four_weeks() {
+    assert_eq!(json!("2419200s"), serialized_ttl(MAX_TTL));
+    assert_eq!(json!("2419200s"), serialized_ttl(MAX_TTL + Duration::from_secs(1)));
+}
+
+#[test]
+fn should_serialize_the_priority_in_uppercase() {
+    for (priority, expected) in [(Priority::High, "HIGH"), (Priority::Normal, "NORMAL")] {
+        let mut builder = AndroidConfigBuilder::new();
+        builder.priority(priority);
+
+        assert_eq!(
+            json!({ "priority": expected }),
+            serde_json::to_value(builder.finalize()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn should_accumulate_the_data() {
+    let mut builder = AndroidConfigBuilder::new();
+    builder
+        .data_entry("b", "2")
+        .unwrap()
+        .data_entry("a", "1")
+        .unwrap()
+        .data_entry("b", "3")
+        .unwrap();
+    let android = builder.finalize();
+
+    assert_eq!(
+        json!({"data": {"a": "1", "b": "3"}}),
+        serde_json::to_value(&android).unwrap()
+    );
+    assert_eq!(Some("3"), android.data().unwrap().get("b").map(String::as_str));
+}
+
+#[test]
+fn should_convert_the_data_to_a_string_map() {
+    let mut builder = AndroidConfigBuilder::new();
+    builder.data_entry("stale", "1").unwrap();
+    builder
+        .data(&json!({"link": "app://matches/42", "score": 2, "live": true, "teams": ["a", "b"]}))
+        .unwrap();
+
+    assert_eq!(
+        json!({"data": {"link": "app://matches/42", "live": "true", "score": "2", "teams": "[\"a\",\"b\"]"}}),
+        serde_json::to_value(builder.finalize()).unwrap()
+    );
+}
+
+#[test]
+fn should_reject_android_data_with_reserved_keys() {
+    for key in ["from", "message_type", "google.ttl", "gcm.notification.title"] {
+        let mut builder = AndroidConfigBuilder::new();
+
+        assert_eq!(
+            Some(BuildError::Validation {
+                field: "android.data",
+                reason: format!("the key `{}` is reserved", key),
+            }),
+            builder.data(&json!({ key: "bar" })).err()
+        );
+        assert_eq!(None, builder.finalize().data());
+    }
+}
+
+#[test]
+fn should_reject_an_android_data_entry_with_a_reserved_key() {
+    for key in ["from", "message_type", "google.ttl", "gcm.notification.title"] {
+        let mut builder = AndroidConfigBuilder::new();
+        builder.data_entry("link", "app://matches/42").unwrap();
+
+        assert_eq!(
+            Some(BuildError::Validation {
+                field: "android.data",
+                reason: format!("the key `{}` is reserved", key),
+            }),
+            builder.data_entry(key, "bar").err()
+        );
+        assert_eq!(
+            json!({"data": {"link": "app://matches/42"}}),
+            serde_json::to_value(builder.finalize()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn should_reject_android_data_which_is_not_a_map() {
+    let mut builder = AndroidConfigBuilder::new();
+
+    assert_eq!(
+        Some(BuildError::Validation {
+            field: "android.data",
+            reason: "not a map".to_string()
+        }),
+        builder.data(&["link"]).err()
+    );
+    assert_eq!(None, builder.finalize().data());
+}
+
+#[test]
+fn should_nest_the_analytics_label_in_the_fcm_options() {
+    let mut builder = AndroidConfigBuilder::new();
+    builder.analytics_label("campaign-42");
+    let android = builder.finalize();
+
+    assert_eq!(Some("campaign-42"), android.analytics_label());
+    assert_eq!(
+        json!({"fcm_options": {"analytics_label": "campaign-42"}}),
+        serde_json::to_value(&android).unwrap()
+    );
+}
+
+#[test]
+fn should_serialize_only_the_set_notification_fields() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification
+        .icon("ic_goal")
+        .color("#1a73e8")
+        .tag("match-42")
+        .click_action("OPEN_MATCH")
+        .ticker("Goal for Australia")
+        .sticky(false)
+        .local_only(true);
+
+    let mut builder = AndroidConfigBuilder::new();
+    builder.notification(notification.finalize());
+
+    assert_eq!(
+        json!({
+            "notification": {
+                "click_action": "OPEN_MATCH",
+                "color": "#1a73e8",
+                "icon": "ic_goal",
+                "local_only": true,
+                "sticky": false,
+                "tag": "match-42",
+                "ticker": "Goal for Australia",
+            },
+        }),
+        serde_json::to_value(builder.finalize()).unwrap()
+    );
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.tag("match-42");
+
+    assert_eq!(
+        json!({"tag": "match-42"}),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+}
+
+#[test]
+fn should_serialize_the_localization_keys_and_args() {
+    let title_args = vec![Cow::from("Australia"), Cow::from("New Zealand".to_string())];
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification
+        .title_loc_key("match_title")
+        .title_loc_args(&title_args)
+        .body_loc_key("match_body")
+        .body_loc_args(&["3", "1"]);
+
+    assert_eq!(
+        json!({
+            "body_loc_args": ["3", "1"],
+            "body_loc_key": "match_body",
+            "title_loc_args": ["Australia", "New Zealand"],
+            "title_loc_key": "match_title",
+        }),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+}
+
+#[test]
+fn should_omit_empty_localization_args() {
+    let no_args: [&str; 0] = [];
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification
+        .title_loc_key("match_title")
+        .title_loc_args(&no_args)
+        .body_loc_key("match_body");
+    let notification = notification.finalize();
+
+    assert_eq!(None, notification.title_loc_args());
+    assert_eq!(
+        json!({"body_loc_key": "match_body", "title_loc_key": "match_title"}),
+        serde_json::to_value(notification).unwrap()
+    );
+}
+
+fn serialized_event_time(event_time: SystemTime) -> serde_json::Value {
+    let mut builder = AndroidNotificationBuilder::new();
+    builder.event_time(event_time).unwrap();
+
+    serde_json::to_value(builder.finalize()).unwrap()["event_time"].clone()
+}
+
+#[test]
+fn should_serialize_the_event_time_with_nanoseconds() {
+    assert_eq!(
+        json!("1970-01-01T00:00:00.000000000Z"),
+        serialized_event_time(UNIX_EPOCH)
+    );
+    assert_eq!(
+        json!("2017-01-15T01:30:15.010000000Z"),
+        serialized_event_time(UNIX_EPOCH + Duration::new(1_484_443_815, 10_000_000))
+    );
+    assert_eq!(
+        json!("2017-01-15T01:30:15.000000001Z"),
+        serialized_event_time(UNIX_EPOCH + Duration::new(1_484_443_815, 1))
+    );
+}
+
+#[test]
+fn should_reject_event_times_before_the_epoch() {
+    let mut builder = AndroidNotificationBuilder::new();
+
+    match builder.event_time(UNIX_EPOCH - Duration::from_secs(5)) {
+        Err(BuildError::Validation { field, reason }) => {
+            assert_eq!("android.notification.event_time", field);
+            assert_eq!("5s before 1970-01-01T00:00:00Z", reason);
+        }
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+
+    assert_eq!(None, builder.finalize().event_time());
+}
+
+#[test]
+fn should_accept_chrono_event_times() {
+    let event_time = "2017-01-15T01:30:15.010Z".parse().unwrap();
+
+    let mut builder = AndroidNotificationBuilder::new();
+    builder.event_time_utc(event_time);
+
+    assert_eq!(Some(event_time), builder.finalize().event_time());
+}
+
+#[test]
+fn should_omit_the_unset_notification_priority_and_visibility() {
+    let notification = AndroidNotificationBuilder::new().finalize();
+
+    assert_eq!(None, notification.notification_priority());
+    assert_eq!(None, notification.visibility());
+    assert_eq!(json!({}), serde_json::to_value(notification).unwrap());
+}
+
+#[test]
+fn should_serialize_the_notification_priority_and_visibility() {
+    let priorities = [
+        (NotificationPriority::Min, "PRIORITY_MIN"),
+        (NotificationPriority::Low, "PRIORITY_LOW"),
+        (NotificationPriority::Default, "PRIORITY_DEFAULT"),
+        (NotificationPriority::High, "PRIORITY_HIGH"),
+        (NotificationPriority::Max, "PRIORITY_MAX"),
+    ];
+
+    for (priority, expected) in priorities {
+        assert_eq!(json!(expected), serde_json::to_value(priority).unwrap());
+    }
+
+    for (visibility, expected) in [
+        (Visibility::Private, "PRIVATE"),
+        (Visibility::Public, "PUBLIC"),
+        (Visibility::Secret, "SECRET"),
+    ] {
+        assert_eq!(json!(expected), serde_json::to_value(visibility).unwrap());
+    }
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification
+        .notification_priority(NotificationPriority::High)
+        .visibility(Visibility::Private);
+
+    let mut builder = AndroidConfigBuilder::new();
+    builder.priority(Priority::Normal).notification(notification.finalize());
+
+    assert_eq!(
+        json!({
+            "notification": {"notification_priority": "PRIORITY_HIGH", "visibility": "PRIVATE"},
+            "priority": "NORMAL",
+        }),
+        serde_json::to_value(builder.finalize()).unwrap()
+    );
+}
+
+#[test]
+fn should_serialize_the_sound() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.sound(Sound::Custom("goal".into()));
+
+    assert_eq!(
+        json!({"sound": "goal"}),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.sound(Sound::Default);
+
+    assert_eq!(
+        json!({"default_sound": true}),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+}
+
+#[test]
+fn should_serialize_the_vibration_and_light_settings() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification
+        .vibrate_timings(&[Duration::ZERO, Duration::from_millis(500), Duration::from_secs(1)])
+        .light_settings(LightSettings::new(
+            Color::from_hex("#ff0000").unwrap(),
+            Duration::from_secs(1),
+            Duration::from_millis(1500),
+        ));
+
+    assert_eq!(
+        json!({
+            "light_settings": {
+                "color": {"red": 1.0, "green": 0.0, "blue": 0.0, "alpha": 1.0},
+                "light_off_duration": "1.500s",
+                "light_on_duration": "1s",
+            },
+            "vibrate_timings": ["0s", "0.500s", "1s"],
+        }),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification
+        .vibrate_timings(&[])
+        .default_vibrate_timings(true)
+        .default_light_settings(true);
+
+    assert_eq!(
+        json!({"default_light_settings": true, "default_vibrate_timings": true}),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+}
+
+#[test]
+fn should_round_trip_hex_colors() {
+    for hex in ["#000000", "#ffffff", "#1a2b3c", "#1a2b3c80", "#00000000"] {
+        assert_eq!(hex, Color::from_hex(hex).unwrap().to_hex());
+    }
+
+    assert_eq!("#abcdef", Color::from_hex("#ABCDEF").unwrap().to_hex());
+    assert_eq!("#abcdef", Color::from_hex("#abcdefff").unwrap().to_hex());
+    assert_eq!(128.0 / 255.0, Color::from_hex("#80808080").unwrap().alpha);
+}
+
+#[test]
+fn should_reject_invalid_hex_colors() {
+    for hex in [
+        "",
+        "#",
+        "ff0000",
+        "#ff000",
+        "#ff00000",
+        "#ff0000000",
+        "#gg0000",
+        "#+f0000",
+    ] {
+        assert_eq!(
+            BuildError::Validation {
+                field: "color",
+                reason: format!("`{}` is not in #rrggbb or #rrggbbaa format", hex)
+            },
+            Color::from_hex(hex).unwrap_err()
+        );
+    }
+}
+
+#[test]
+fn should_serialize_the_notification_count() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.notification_count(3);
+
+    assert_eq!(
+        json!({"notification_count": 3}),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.notification_count(0);
+
+    assert_eq!(
+        json!({"notification_count": 0}),
+        serde_json::to_value(notification.finalize()).unwrap()
+    );
+
+    assert_eq!(
+        json!({}),
+        serde_json::to_value(AndroidNotificationBuilder::new().finalize()).unwrap()
+    );
+}
@@ -0,0 +1,217 @@
+use crate::message::{BuildError, FcmOptions};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod tests;
+
+/// The largest payload APNs accepts, in bytes.
+const MAX_PAYLOAD_BYTES: usize = 4096;
+
+/// The largest payload APNs accepts for a VoIP notification, in bytes.
+const MAX_VOIP_PAYLOAD_BYTES: usize = 5120;
+
+/// The APNs specific options of a message. Use the corresponding
+/// `ApnsConfigBuilder` to get an instance, and set it with
+/// `FCMRequestBuilder::apns`.
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct ApnsConfig<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fcm_options: Option<FcmOptions<'a>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<BTreeMap<Cow<'a, str>, Cow<'a, str>>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<Value>,
+}
+
+impl<'a> ApnsConfig<'a> {
+    /// The FCM options of the message on Apple devices.
+    pub fn fcm_options(&self) -> Option<&FcmOptions<'a>> {
+        self.fcm_options.as_ref()
+    }
+
+    /// The HTTP headers sent to APNs, e.g. `apns-priority`, sorted by name.
+    pub fn headers(&self) -> Option<&BTreeMap<Cow<'a, str>, Cow<'a, str>>> {
+        self.headers.as_ref()
+    }
+
+    /// The APNs payload of the message, including its `aps` dictionary.
+    pub fn payload(&self) -> Option<&Value> {
+        self.payload.as_ref()
+    }
+
+    /// Set the `content-available` and `mutable-content` keys of the `aps`
+    /// dictionary of the payload, as the 0 or 1 Apple expects, keeping the
+    /// rest of the payload.
+    pub(crate) fn with_aps_flags(
+        mut self,
+        content_available: Option<bool>,
+        mutable_content: Option<bool>,
+    ) -> ApnsConfig<'a> {
+        if content_available.is_none() && mutable_content.is_none() {
+            return self;
+        }
+
+        let aps = self.aps_mut();
+
+        for (key, flag) in [
+            ("content-available", content_available),
+            ("mutable-content", mutable_content),
+        ] {
+            if let Some(flag) = flag {
+                aps.insert(key.to_string(), Value::from(u8::from(flag)));
+            }
+        }
+
+        self
+    }
+
+    /// Make the payload a background notification setting the badge of the
+    /// app to 0, keeping the rest of the payload. A background notification
+    /// also needs the `apns-push-type` and `apns-priority` headers APNs
+    /// expects for it since iOS 13, which replace the ones already set.
+    pub(crate) fn with_badge_cleared(mut self) -> ApnsConfig<'a> {
+        let aps = self.aps_mut();
+        aps.insert("badge".to_string(), Value::from(0));
+        aps.insert("content-available".to_string(), Value::from(1));
+
+        let headers = self.headers.get_or_insert_with(BTreeMap::new);
+
+        for (name, value) in [("apns-push-type", "background"), ("apns-priority", "5")] {
+            headers.retain(|header, _| !header.eq_ignore_ascii_case(name));
+            headers.insert(Cow::Borrowed(name), Cow::Borrowed(value));
+        }
+
+        self
+    }
+
+    /// The string fields `FCMRequestBuilder::empty_strings` applies to, by
+    /// path in the message.
+    pub(crate) fn string_fields(&mut self) -> Vec<(&'static str, &mut Option<&'a str>)> {
+        self.fcm_options
+            .iter_mut()
+            .map(|fcm_options| ("apns.fcm_options.analytics_label", &mut fcm_options.analytics_label))
+            .collect()
+    }
+
+    /// The largest payload APNs accepts for the message: 5120 bytes when its
+    /// `apns-push-type` header is `voip`, 4096 bytes otherwise.
+    pub(crate) fn max_payload_bytes(&self) -> usize {
+        let voip = self
+            .headers
+            .iter()
+            .flatten()
+            .any(|(name, value)| name.eq_ignore_ascii_case("apns-push-type") && value.eq_ignore_ascii_case("voip"));
+
+        if voip {
+            MAX_VOIP_PAYLOAD_BYTES
+        } else {
+            MAX_PAYLOAD_BYTES
+        }
+    }
+
+    /// Whether the `aps` dictionary of the payload has an alert to show.
+    pub(crate) fn has_alert(&self) -> bool {
+        self.payload
+            .as_ref()
+            .is_some_and(|payload| !payload["aps"]["alert"].is_null())
+    }
+
+    /// The `aps` dictionary of the payload, created if missing.
+    fn aps_mut(&mut self) -> &mut Map<String, Value> {
+        let payload = self.payload.get_or_insert_with(|| Value::Object(Map::new()));
+
+        if !payload.is_object() {
+            *payload = Value::Object(Map::new());
+        }
+
+        let aps = payload
+            .as_object_mut()
+            .unwrap()
+            .entry("aps")
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if !aps.is_object() {
+            *aps = Value::Object(Map::new());
+        }
+
+        aps.as_object_mut().unwrap()
+    }
+}
+
+/// A builder to get an `ApnsConfig` instance.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{ApnsConfigBuilder, FCMRequestBuilder};
+/// use serde_json::json;
+///
+/// let mut builder = ApnsConfigBuilder::new();
+/// builder.header("apns-priority", "5");
+/// builder.payload(&json!({"aps": {"category": "NEW_MESSAGE"}})).unwrap();
+/// let apns = builder.finalize();
+///
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+/// builder.apns(apns).content_available(true);
+/// let message = builder.finalize();
+/// ```
+#[derive(Default)]
+pub struct ApnsConfigBuilder<'a> {
+    fcm_options: Option<FcmOptions<'a>>,
+    headers: Option<BTreeMap<Cow<'a, str>, Cow<'a, str>>>,
+    payload: Option<Value>,
+}
+
+impl<'a> ApnsConfigBuilder<'a> {
+    /// Get a new `ApnsConfigBuilder` instance, with no options set.
+    pub fn new() -> ApnsConfigBuilder<'a> {
+        Self::default()
+    }
+
+    /// Set the APNs payload of the message, which must serialize to a JSON
+    /// object.
+    pub fn payload(&mut self, payload: &dyn erased_serde::Serialize) -> Result<&mut Self, BuildError> {
+        let payload = serde_json::to_value(payload)?;
+
+        if !payload.is_object() {
+            return Err(BuildError::validation("apns.payload", "not an object"));
+        }
+
+        self.payload = Some(payload);
+        Ok(self)
+    }
+
+    /// Add an HTTP header sent to APNs, e.g. `apns-priority`, `apns-topic`
+    /// or `apns-collapse-id`, replacing the value of a header of the same
+    /// name. Can be called several times.
+    pub fn header<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+    {
+        self.headers
+            .get_or_insert_with(BTreeMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the FCM options of the message on Apple devices.
+    pub fn fcm_options(&mut self, fcm_options: FcmOptions<'a>) -> &mut Self {
+        self.fcm_options = Some(fcm_options);
+        self
+    }
+
+    /// Complete the build and get an `ApnsConfig` instance
+    pub fn finalize(self) -> ApnsConfig<'a> {
+        ApnsConfig {
+            fcm_options: self.fcm_options,
+            headers: self.headers,
+            payload: self.payload,
+        }
+    }
+}
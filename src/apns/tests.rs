@@ -0,0 +1,79 @@
+use crate::apns::{ApnsConfig, ApnsConfigBuilder};
+use crate::BuildError;
+use serde_json::json;
+
+#[test]
+fn should_not_serialize_an_unset_payload() {
+    let apns = ApnsConfigBuilder::new().finalize();
+
+    assert_eq!(json!({}), serde_json::to_value(apns).unwrap());
+}
+
+#[test]
+fn should_reject_a_payload_which_is_not_an_object() {
+    let mut builder = ApnsConfigBuilder::new();
+
+    assert_eq!(
+        Some(BuildError::Validation {
+            field: "apns.payload",
+            reason: "not an object".to_string()
+        }),
+        builder.payload(&"aps").err()
+    );
+    assert_eq!(None, builder.finalize().payload());
+}
+
+#[test]
+fn should_set_the_aps_flags_as_integers() {
+    let apns = ApnsConfig::default().with_aps_flags(Some(true), Some(false));
+
+    assert_eq!(
+        json!({"payload": {"aps": {"content-available": 1, "mutable-content": 0}}}),
+        serde_json::to_value(apns).unwrap()
+    );
+
+    assert_eq!(ApnsConfig::default(), ApnsConfig::default().with_aps_flags(None, None));
+}
+
+#[test]
+fn should_keep_the_custom_payload_when_setting_the_aps_flags() {
+    let mut builder = ApnsConfigBuilder::new();
+    builder
+        .payload(&json!({"aps": {"category": "NEW_MESSAGE"}, "thread": "42"}))
+        .unwrap();
+
+    let apns = builder.finalize().with_aps_flags(Some(true), None);
+
+    assert_eq!(
+        json!({"payload": {"aps": {"category": "NEW_MESSAGE", "content-available": 1}, "thread": "42"}}),
+        serde_json::to_value(apns).unwrap()
+    );
+}
+
+#[test]
+fn should_serialize_the_headers_sorted() {
+    let mut builder = ApnsConfigBuilder::new();
+    builder
+        .header("apns-topic", "com.example.scores")
+        .header("apns-collapse-id", String::from("match-42"))
+        .header("apns-priority", "10")
+        .header("apns-priority", "5");
+    let apns = builder.finalize();
+
+    assert_eq!(
+        r#"{"headers":{"apns-collapse-id":"match-42","apns-priority":"5","apns-topic":"com.example.scores"}}"#,
+        serde_json::to_string(&apns).unwrap()
+    );
+    assert_eq!(3, apns.headers().unwrap().len());
+}
+
+#[test]
+fn should_not_serialize_unset_headers() {
+    let mut builder = ApnsConfigBuilder::new();
+    builder.payload(&json!({"aps": {"badge": 1}})).unwrap();
+
+    assert_eq!(
+        json!({"payload": {"aps": {"badge": 1}}}),
+        serde_json::to_value(builder.finalize()).unwrap()
+    );
+}
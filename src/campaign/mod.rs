@@ -0,0 +1,66 @@
+//! Helpers to split a campaign send across several workers.
+
+#[cfg(test)]
+mod tests;
+
+/// What `Client::send_multi` would send, from `Client::estimate`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CampaignEstimate {
+    /// How many tokens are sent to, after deduplication.
+    pub tokens: usize,
+
+    /// How many requests are made, without the retries.
+    pub requests: usize,
+
+    /// How many batches the tokens are sent in.
+    pub batches: usize,
+
+    /// The total size of the request bodies, without the retries.
+    pub payload_bytes: usize,
+
+    /// How many retries are expected at the given failure rate.
+    pub expected_retries: f64,
+}
+
+/// Iterate over the tokens assigned to the worker `shard_index` out of
+/// `shard_count` workers.
+///
+/// A token is assigned from a stable hash of its value, so every token goes
+/// to exactly one shard, and running again with the same parameters gives the
+/// same assignment whatever the order of the tokens.
+///
+/// # Panics
+///
+/// Panics if `shard_index` is not lower than `shard_count`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::campaign;
+///
+/// let tokens = ["token1", "token2", "token3"];
+/// let mine: Vec<_> = campaign::shard(&tokens, 0, 2).collect();
+/// ```
+pub fn shard<T>(tokens: &[T], shard_index: usize, shard_count: usize) -> impl Iterator<Item = &T>
+where
+    T: AsRef<str>,
+{
+    assert!(
+        shard_index < shard_count,
+        "shard index {} out of {} shards",
+        shard_index,
+        shard_count
+    );
+
+    tokens
+        .iter()
+        .filter(move |token| fnv1a(token.as_ref().as_bytes()) % shard_count as u64 == shard_index as u64)
+}
+
+/// The 64 bits FNV-1a hash of `bytes`, which unlike the std hashers is
+/// guaranteed to be the same across runs and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,47 @@
+use crate::campaign::{fnv1a, shard};
+use std::collections::HashSet;
+
+fn tokens() -> Vec<String> {
+    (0..1000).map(|i| format!("token-{}", i)).collect()
+}
+
+#[test]
+fn should_hash_with_fnv1a() {
+    assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a(b""));
+    assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a(b"a"));
+}
+
+#[test]
+fn should_assign_every_token_to_exactly_one_shard() {
+    let tokens = tokens();
+    let mut assigned = HashSet::new();
+
+    for shard_index in 0..7 {
+        for token in shard(&tokens, shard_index, 7) {
+            assert!(assigned.insert(token));
+        }
+    }
+
+    assert_eq!(tokens.len(), assigned.len());
+}
+
+#[test]
+fn should_assign_tokens_whatever_their_order() {
+    let tokens = tokens();
+    let mut reversed = tokens.clone();
+    reversed.reverse();
+
+    for shard_index in 0..3 {
+        let first: HashSet<_> = shard(&tokens, shard_index, 3).collect();
+        let second: HashSet<_> = shard(&reversed, shard_index, 3).collect();
+
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+    }
+}
+
+#[test]
+#[should_panic(expected = "shard index 2 out of 2 shards")]
+fn should_reject_out_of_range_shard_indexes() {
+    let _ = shard(&["token"], 2, 2);
+}
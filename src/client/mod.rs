@@ -1,14 +1,57 @@
 pub mod response;
+mod retry;
+
+#[cfg(test)]
+mod tests;
 
 pub use crate::client::response::*;
+pub use crate::client::retry::{Jitter, RetryPolicy};
+
+use crate::campaign::CampaignEstimate;
+use crate::message::{envelope, is_valid_project_id, FCMRequest};
+use futures_util::stream::{self, StreamExt};
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Body, StatusCode, Url};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{error::Error, fmt};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// The response header identifying a notification in Apple's delivery logs.
+const APNS_UNIQUE_ID: &str = "apns-unique-id";
+
+/// How many requests `Client::send_multi` sends at the same time, unless
+/// `ClientBuilder::multicast_concurrency` says otherwise.
+const DEFAULT_MULTICAST_CONCURRENCY: usize = 10;
 
-use crate::message::FCMRequest;
-use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER};
-use reqwest::{Body, StatusCode};
+/// How many tokens `Client::send_multi` sends to in a batch, unless
+/// `ClientBuilder::multicast_batch_size` says otherwise.
+const DEFAULT_MULTICAST_BATCH_SIZE: usize = 500;
+
+/// The FCM endpoint used unless `ClientBuilder::base_url` says otherwise.
+const DEFAULT_BASE_URL: &str = "https://fcm.googleapis.com";
 
 /// An async client for sending the notification payload.
+///
+/// Cloning a `Client` is cheap, so a single client can be cloned into every
+/// task instead of being wrapped in an `Arc`. The clones share the same
+/// connection pool and the same budget of `ClientBuilder::max_inflight_bytes`;
+/// use `Client::detached` for a copy with a budget of its own.
+#[derive(Clone)]
 pub struct Client {
     http_client: reqwest::Client,
+    base_url: String,
+    pipeline: SendPipeline,
+    retry_policy: RetryPolicy,
+    multicast_concurrency: usize,
+    multicast_batch_size: usize,
+    dedupe_tokens: bool,
+    retry_empty_success_body: bool,
+    inflight_budget: Option<Arc<InflightBudget>>,
+    http1_fallback: Option<Arc<Http1Fallback>>,
 }
 
 impl Default for Client {
@@ -17,33 +60,853 @@ impl Default for Client {
     }
 }
 
+/// A builder to get a `Client` with a non-default configuration.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::ClientBuilder;
+///
+/// let mut builder = ClientBuilder::new();
+/// builder.base_url("https://fcm.test");
+/// builder.resolve("fcm.test", "127.0.0.1:8443".parse().unwrap());
+/// let client = builder.build().unwrap();
+/// ```
+pub struct ClientBuilder {
+    base_url: String,
+    resolve: Vec<(String, SocketAddr)>,
+    pipeline: SendPipeline,
+    retry_policy: RetryPolicy,
+    multicast_concurrency: usize,
+    multicast_batch_size: usize,
+    dedupe_tokens: bool,
+    retry_empty_success_body: bool,
+    max_inflight_bytes: Option<usize>,
+    http1_only: bool,
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+    tls_sni: bool,
+    /// The certificates of the TLS mock servers of the tests.
+    #[cfg(all(test, any(feature = "native-tls", feature = "rustls", feature = "vendored-tls")))]
+    root_certificates: Vec<reqwest::Certificate>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    /// Get a new `ClientBuilder` instance, with the default configuration.
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            resolve: Vec::new(),
+            pipeline: SendPipeline::Direct,
+            retry_policy: RetryPolicy::standard(),
+            multicast_concurrency: DEFAULT_MULTICAST_CONCURRENCY,
+            multicast_batch_size: DEFAULT_MULTICAST_BATCH_SIZE,
+            dedupe_tokens: false,
+            retry_empty_success_body: false,
+            max_inflight_bytes: None,
+            http1_only: false,
+            #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+            tls_sni: true,
+            #[cfg(all(test, any(feature = "native-tls", feature = "rustls", feature = "vendored-tls")))]
+            root_certificates: Vec::new(),
+        }
+    }
+
+    /// Send the requests to another server than `https://fcm.googleapis.com`,
+    /// e.g. a local stub when testing.
+    pub fn base_url(&mut self, base_url: &str) -> &mut Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Resolve `host` to the given address instead of using the system DNS.
+    /// The port of the address is ignored, the one of the URL is used.
+    pub fn resolve(&mut self, host: &str, addr: SocketAddr) -> &mut Self {
+        self.resolve.push((host.to_string(), addr));
+        self
+    }
+
+    /// Set how `Client::send` sends the messages. Defaults to
+    /// `SendPipeline::Direct`.
+    pub fn pipeline(&mut self, pipeline: SendPipeline) -> &mut Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Set the policy to retry the messages with. Defaults to
+    /// `RetryPolicy::standard()`. The client does not retry on its own, the
+    /// policy only affects `Client::estimate`, and is held for the retry loop
+    /// of the caller. `build` fails if its `base_delay` is longer than its
+    /// `max_delay`.
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set how many requests `Client::send_multi` sends at the same time, at
+    /// least one. Defaults to 10.
+    pub fn multicast_concurrency(&mut self, multicast_concurrency: usize) -> &mut Self {
+        self.multicast_concurrency = multicast_concurrency.max(1);
+        self
+    }
+
+    /// Set how many tokens `Client::send_multi` sends to before checking
+    /// the batch did not fail as a whole, at least one. Defaults to 500.
+    pub fn multicast_batch_size(&mut self, multicast_batch_size: usize) -> &mut Self {
+        self.multicast_batch_size = multicast_batch_size.max(1);
+        self
+    }
+
+    /// Set to `true` for `Client::send_multi` to send only once to a token
+    /// given more than once. The duplicates get the result of that send.
+    /// Defaults to `false`.
+    pub fn dedupe_tokens(&mut self, dedupe_tokens: bool) -> &mut Self {
+        self.dedupe_tokens = dedupe_tokens;
+        self
+    }
+
+    /// Set to `true` to report a success response with an empty body as a
+    /// `FcmError::ServerError`, to be retried, instead of a
+    /// `FcmError::EmptySuccessBody`. Defaults to `false`, as the message may
+    /// have been accepted and retrying it can deliver it twice.
+    pub fn retry_empty_success_body(&mut self, retry_empty_success_body: bool) -> &mut Self {
+        self.retry_empty_success_body = retry_empty_success_body;
+        self
+    }
+
+    /// Limit the bytes of the request bodies in flight at the same time, over
+    /// the client and all its clones, but not its `Client::detached` copies.
+    /// A send waits until its body fits in the budget, in the order the sends
+    /// started, and frees it once the response is read. A body larger than
+    /// the whole budget waits for it to be free and is then sent alone. Not
+    /// limited by default.
+    pub fn max_inflight_bytes(&mut self, max_inflight_bytes: usize) -> &mut Self {
+        self.max_inflight_bytes = Some(max_inflight_bytes);
+        self
+    }
+
+    /// Set to `true` to only use HTTP/1.1, e.g. behind egress proxies that
+    /// break the HTTP/2 negotiation. Disabled by default, in which case the
+    /// client falls back to HTTP/1.1 on its own once a request fails over
+    /// HTTP/2, see `Client::is_downgraded_to_http1`.
+    pub fn http1_only(&mut self, http1_only: bool) -> &mut Self {
+        self.http1_only = http1_only;
+        self
+    }
+
+    /// Set to `false` to not send the TLS Server Name Indication extension.
+    /// Enabled by default.
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+    pub fn tls_sni(&mut self, tls_sni: bool) -> &mut Self {
+        self.tls_sni = tls_sni;
+        self
+    }
+
+    /// Complete the build and get a `Client` instance, or the first invalid
+    /// setting found.
+    pub fn build(self) -> Result<Client, ConfigError> {
+        let url = Url::parse(&self.base_url).map_err(|e| ConfigError::InvalidBaseUrl {
+            base_url: self.base_url.clone(),
+            reason: e.to_string(),
+        })?;
+
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ConfigError::InvalidBaseUrl {
+                base_url: self.base_url,
+                reason: "the scheme must be http or https".to_string(),
+            });
+        }
+
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err(ConfigError::InvalidBaseUrl {
+                base_url: self.base_url,
+                reason: "the URL must not have a query nor a fragment".to_string(),
+            });
+        }
+
+        if let Some((host, _)) = self
+            .resolve
+            .iter()
+            .find(|(host, _)| host.is_empty() || host.contains('/'))
+        {
+            return Err(ConfigError::InvalidResolveHost(host.clone()));
+        }
+
+        if self.retry_policy.base_delay() > self.retry_policy.max_delay() {
+            return Err(ConfigError::InvalidRetryPolicy {
+                field: "retry_policy.base_delay",
+                reason: format!(
+                    "{:?} is longer than the max_delay of {:?}",
+                    self.retry_policy.base_delay(),
+                    self.retry_policy.max_delay()
+                ),
+            });
+        }
+
+        let http_client_with = |http1_only: bool| {
+            let mut http_client = reqwest::ClientBuilder::new().pool_max_idle_per_host(usize::MAX);
+
+            for (host, addr) in &self.resolve {
+                http_client = http_client.resolve(host, *addr);
+            }
+
+            if http1_only {
+                http_client = http_client.http1_only();
+            }
+
+            #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+            {
+                http_client = http_client.tls_sni(self.tls_sni);
+            }
+
+            #[cfg(all(test, any(feature = "native-tls", feature = "rustls", feature = "vendored-tls")))]
+            for certificate in &self.root_certificates {
+                http_client = http_client.add_root_certificate(certificate.clone());
+            }
+
+            http_client.build().map_err(|e| ConfigError::HttpClient(e.to_string()))
+        };
+
+        let http1_fallback = match self.http1_only {
+            true => None,
+            false => Some(Arc::new(Http1Fallback::new(http_client_with(true)?))),
+        };
+        let http_client = http_client_with(self.http1_only)?;
+
+        Ok(Client {
+            http_client,
+            base_url: self.base_url,
+            pipeline: self.pipeline,
+            retry_policy: self.retry_policy,
+            multicast_concurrency: self.multicast_concurrency,
+            multicast_batch_size: self.multicast_batch_size,
+            dedupe_tokens: self.dedupe_tokens,
+            retry_empty_success_body: self.retry_empty_success_body,
+            inflight_budget: self.max_inflight_bytes.map(|max| Arc::new(InflightBudget::new(max))),
+            http1_fallback,
+        })
+    }
+}
+
+/// How `Client::send` sends a message.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SendPipeline {
+    /// Send the message in a single request.
+    Direct,
+
+    /// First ask FCM to validate the message, and only send it if the
+    /// validation succeeded. A failed validation is returned as the error of
+    /// `send`.
+    ValidateThenSend,
+}
+
+/// The headers set by the client itself, which `SendOptions::header` cannot
+/// override.
+const RESERVED_HEADERS: [HeaderName; 3] = [AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE];
+
+/// Options applying to a single `Client::send_with` call.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use fcm_http1::{Client, FCMRequestBuilder, SendOptions};
+///
+/// let mut options = SendOptions::new();
+/// options.header("x-proxy-authorization", "<proxy token>")?;
+///
+/// let builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+/// let response = Client::new().send_with(builder.finalize(), &options).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The options serialize as `{"headers": [["<name>", "<value>"], ...]}`, so
+/// they can be handed from a service to another. When deserializing, unknown
+/// fields are ignored, missing ones are empty, and the headers are checked
+/// like by `SendOptions::header`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SendOptions {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// The serialized form of `SendOptions`.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SendOptionsFields {
+    headers: Vec<(String, String)>,
+}
+
+impl Serialize for SendOptions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                // The values are only set from strings.
+                (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned())
+            })
+            .collect();
+
+        SendOptionsFields { headers }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SendOptions {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let fields = SendOptionsFields::deserialize(deserializer)?;
+        let mut options = SendOptions::new();
+
+        for (name, value) in &fields.headers {
+            options.header(name, value).map_err(serde::de::Error::custom)?;
+        }
+
+        Ok(options)
+    }
+}
+
+impl SendOptions {
+    /// Get a new `SendOptions` instance, with no options.
+    pub fn new() -> SendOptions {
+        Self::default()
+    }
+
+    /// Add a header to the request, e.g. for an egress proxy. Can be called
+    /// several times. The headers set by the client (`Authorization`,
+    /// `Content-Length` and `Content-Type`) cannot be overridden.
+    pub fn header(&mut self, name: &str, value: &str) -> Result<&mut Self, ConfigError> {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| ConfigError::InvalidHeader(name.to_string()))?;
+
+        if RESERVED_HEADERS.contains(&name) {
+            return Err(ConfigError::ReservedHeader(name.to_string()));
+        }
+
+        let value = HeaderValue::from_str(value).map_err(|_| ConfigError::InvalidHeader(name.to_string()))?;
+
+        self.headers.push((name, value));
+        Ok(self)
+    }
+
+    /// Apply `overrides` on top of these options: the headers of `overrides`
+    /// replace all the ones of the same name, the others are kept.
+    pub fn merge(&mut self, overrides: &SendOptions) -> &mut Self {
+        self.headers
+            .retain(|(name, _)| overrides.headers.iter().all(|(overridden, _)| overridden != name));
+        self.headers.extend(overrides.headers.iter().cloned());
+        self
+    }
+}
+
+/// An invalid `ClientBuilder` or `SendOptions` setting.
+#[derive(PartialEq, Debug)]
+pub enum ConfigError {
+    /// The `base_url` is not an absolute http(s) URL without query nor
+    /// fragment.
+    InvalidBaseUrl { base_url: String, reason: String },
+
+    /// A host given to `resolve` is not a host name.
+    InvalidResolveHost(String),
+
+    /// A setting of the `retry_policy` is out of range, e.g. a `base_delay`
+    /// longer than the `max_delay`.
+    InvalidRetryPolicy { field: &'static str, reason: String },
+
+    /// The underlying HTTP client could not be created, e.g. because the TLS
+    /// backend failed to initialize.
+    HttpClient(String),
+
+    /// A header given to `SendOptions::header` has an invalid name or value.
+    InvalidHeader(String),
+
+    /// A header given to `SendOptions::header` is set by the client itself.
+    ReservedHeader(String),
+}
+
+impl Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidBaseUrl {
+                ref base_url,
+                ref reason,
+            } => write!(f, "invalid base_url {:?}: {}", base_url, reason),
+            ConfigError::InvalidResolveHost(ref host) => write!(f, "invalid resolve host {:?}", host),
+            ConfigError::InvalidRetryPolicy { field, ref reason } => write!(f, "invalid {}: {}", field, reason),
+            ConfigError::HttpClient(ref reason) => write!(f, "cannot create the HTTP client: {}", reason),
+            ConfigError::InvalidHeader(ref name) => write!(f, "invalid header {:?}", name),
+            ConfigError::ReservedHeader(ref name) => write!(f, "the header {:?} cannot be overridden", name),
+        }
+    }
+}
+
+/// A request validated by FCM with `Client::validate`, holding the serialized
+/// message so it can be sent with `Client::send_validated`.
+#[derive(Debug)]
+pub struct ValidatedRequest<'a> {
+    api_key: &'a str,
+    project: &'a str,
+    message: Vec<u8>,
+    response: FcmResponse,
+}
+
+impl<'a> ValidatedRequest<'a> {
+    /// The response of FCM to the validation request.
+    pub fn response(&self) -> &FcmResponse {
+        &self.response
+    }
+}
+
+/// The bytes the requests of a client and its clones can have in flight.
+struct InflightBudget {
+    permits: Semaphore,
+    max_bytes: u32,
+    used: AtomicUsize,
+}
+
+/// The part of an `InflightBudget` taken by a request, given back on drop.
+struct InflightPermit<'a> {
+    _permit: SemaphorePermit<'a>,
+    bytes: usize,
+    used: &'a AtomicUsize,
+}
+
+impl InflightBudget {
+    fn new(max_bytes: usize) -> InflightBudget {
+        let max_bytes = max_bytes.clamp(1, u32::MAX as usize) as u32;
+
+        InflightBudget {
+            permits: Semaphore::new(max_bytes as usize),
+            max_bytes,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for `bytes` to fit in the budget, or for the whole budget when
+    /// they do not. The semaphore is fair, so the waiters are served in
+    /// order and a large body is not starved by smaller ones.
+    async fn acquire(&self, bytes: usize) -> InflightPermit<'_> {
+        let bytes = bytes.min(self.max_bytes as usize);
+
+        // The semaphore is never closed.
+        let permit = self.permits.acquire_many(bytes as u32).await.unwrap();
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+
+        InflightPermit {
+            _permit: permit,
+            bytes,
+            used: &self.used,
+        }
+    }
+
+    /// The bytes taken by the requests in flight. Not the permits missing
+    /// from the semaphore, which also counts the ones given to a waiter
+    /// before it has all it asked for.
+    fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for InflightPermit<'_> {
+    fn drop(&mut self) {
+        self.used.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// The HTTP/1.1 client a client and its clones fall back to when HTTP/2
+/// fails.
+struct Http1Fallback {
+    http_client: reqwest::Client,
+    downgraded: AtomicBool,
+    downgrades: AtomicUsize,
+}
+
+impl Http1Fallback {
+    fn new(http_client: reqwest::Client) -> Http1Fallback {
+        Http1Fallback {
+            http_client,
+            downgraded: AtomicBool::new(false),
+            downgrades: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Whether `error` comes from the HTTP/2 connection failing, e.g. behind a
+/// proxy breaking it, or from the server refusing the stream. Not from a
+/// stream reset otherwise, as the server may have processed the request.
+fn is_http2_failure(error: &reqwest::Error) -> bool {
+    std::iter::successors(error.source(), |&error| error.source())
+        .find_map(|error| error.downcast_ref::<h2::Error>())
+        .is_some_and(|error| !error.is_reset() || error.reason() == Some(h2::Reason::REFUSED_STREAM))
+}
+
 impl Client {
     /// Get a new instance of Client.
     pub fn new() -> Client {
-        let http_client = reqwest::ClientBuilder::new()
-            .pool_max_idle_per_host(std::usize::MAX)
-            .build()
-            .unwrap();
+        ClientBuilder::new().build().unwrap()
+    }
+
+    /// The policy to retry the messages failing with a
+    /// `FcmError::ServerError` with, for the retry loop of the caller:
+    /// `Client::send` and `Client::send_multi` send each message once.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// The bytes of the request bodies in flight, counted against
+    /// `ClientBuilder::max_inflight_bytes`. Always zero when the client has
+    /// no such budget.
+    pub fn inflight_bytes(&self) -> usize {
+        self.inflight_budget.as_ref().map_or(0, |budget| budget.used())
+    }
+
+    /// Whether the client and its clones send over HTTP/1.1 since a request
+    /// failed over HTTP/2. The client only sends to the host of its base URL,
+    /// so it keeps using HTTP/1.1 for all the following requests. Always
+    /// `false` with `ClientBuilder::http1_only`, which has no fallback.
+    pub fn is_downgraded_to_http1(&self) -> bool {
+        self.http1_fallback
+            .as_ref()
+            .is_some_and(|fallback| fallback.downgraded.load(Ordering::Relaxed))
+    }
+
+    /// How many requests of the client and its clones failed over HTTP/2 and
+    /// were sent again over HTTP/1.1. More than one when requests in flight
+    /// at the same time failed before the client was downgraded.
+    pub fn http1_downgrades(&self) -> usize {
+        self.http1_fallback
+            .as_ref()
+            .map_or(0, |fallback| fallback.downgrades.load(Ordering::Relaxed))
+    }
+
+    /// A copy of the client sharing its connection pool, but with a budget
+    /// of in-flight bytes of its own, of the same size. The sends of the copy
+    /// and its clones do not wait for the ones of the client, nor the other
+    /// way around.
+    pub fn detached(&self) -> Client {
+        Client {
+            inflight_budget: self
+                .inflight_budget
+                .as_ref()
+                .map(|budget| Arc::new(InflightBudget::new(budget.max_bytes as usize))),
+            ..self.clone()
+        }
+    }
+
+    /// The URL to send messages of `project` to.
+    fn send_url(&self, project: &str) -> Result<String, FcmError> {
+        if !is_valid_project_id(project) {
+            return Err(FcmError::InvalidProjectId(project.to_string()));
+        }
 
-        Client { http_client }
+        Ok(format!("{}/v1/projects/{}/messages:send", self.base_url, project))
     }
 
-    /// Try sending a `FCMRequest` to FCM.
+    /// Try sending a `FCMRequest` to FCM, validating it first if the client
+    /// was built with `SendPipeline::ValidateThenSend`.
     pub async fn send(&self, message: FCMRequest<'_>) -> Result<FcmResponse, FcmError> {
-        let payload = serde_json::to_vec(&message.body).unwrap();
-
-        let request = self
-            .http_client
-            .post(format!(
-                "https://fcm.googleapis.com/v1/projects/{}/messages:send",
-                message.project
-            ))
-            .header(CONTENT_TYPE, "application/json")
+        self.send_with(message, &SendOptions::new()).await
+    }
+
+    /// Same as `send`, with options applying to this message only.
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use fcm_http1::{Client, FCMRequestBuilder, SendOptions};
+    ///
+    /// let client = Client::new();
+    /// let builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+    ///
+    /// let mut options = SendOptions::new();
+    /// options.header("x-request-id", "42")?;
+    ///
+    /// let response = client.send_with(builder.finalize(), &options).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_with(&self, message: FCMRequest<'_>, options: &SendOptions) -> Result<FcmResponse, FcmError> {
+        if self.pipeline == SendPipeline::ValidateThenSend {
+            let validated = self.validate_with(message, &options.headers).await?;
+
+            return self
+                .post(
+                    validated.api_key,
+                    validated.project,
+                    envelope(false, &validated.message),
+                    &options.headers,
+                )
+                .await;
+        }
+
+        let payload = message.payload().unwrap();
+
+        self.post(message.api_key, message.project, payload, &options.headers)
+            .await
+    }
+
+    /// Send a copy of `message` to each of the `tokens`, replacing its target,
+    /// as the v1 API has no multicast. The results are in the order of the
+    /// tokens, and an empty list of tokens sends nothing.
+    ///
+    /// The tokens are split into batches of
+    /// `ClientBuilder::multicast_batch_size`, sent one after the other. The
+    /// messages of a batch are sent `ClientBuilder::multicast_concurrency` at
+    /// a time, and a failed message does not stop the others. When a whole
+    /// batch fails with the same error of the client, e.g.
+    /// `FcmError::Unauthorized`, the following batches are not sent and their
+    /// tokens fail with that error too.
+    ///
+    /// With `ClientBuilder::dedupe_tokens`, a token given more than once is
+    /// sent to once, in the batch of its first occurrence, and all its
+    /// occurrences get that result.
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use fcm_http1::{Client, FCMRequestBuilder};
+    ///
+    /// let client = Client::new();
+    /// let builder = FCMRequestBuilder::to_topic("<FCM API Key>", "<project>", "<topic>", None);
+    ///
+    /// let response = client.send_multi(builder.finalize(), &["<token 1>", "<token 2>"]).await;
+    ///
+    /// for token in response.unregistered_tokens() {
+    ///     println!("remove {}", token);
+    /// }
+    /// # }
+    /// ```
+    pub async fn send_multi<T>(&self, message: FCMRequest<'_>, tokens: &[T]) -> MulticastResponse
+    where
+        T: AsRef<str>,
+    {
+        let (sent_tokens, indexes) = self.multicast_tokens(tokens);
+        let results = self.send_batches(&message, &sent_tokens).await;
+
+        MulticastResponse {
+            results: match indexes {
+                Some(indexes) => indexes.into_iter().map(|index| results[index].clone()).collect(),
+                None => results,
+            },
+        }
+    }
+
+    /// Estimate what `send_multi` would send for the same `message` and
+    /// `tokens`, without sending anything. The retries are estimated from
+    /// the `expected_failure_rate` of the requests, between 0 and 1, and the
+    /// retry policy of the client.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// use fcm_http1::{Client, FCMRequestBuilder};
+    ///
+    /// let client = Client::new();
+    /// let message = FCMRequestBuilder::to_topic("<FCM API Key>", "<project>", "<topic>", None).finalize();
+    ///
+    /// let estimate = client.estimate(&message, &["<token 1>", "<token 2>"], 0.01);
+    /// assert_eq!(2, estimate.requests);
+    /// ```
+    pub fn estimate<T>(&self, message: &FCMRequest<'_>, tokens: &[T], expected_failure_rate: f64) -> CampaignEstimate
+    where
+        T: AsRef<str>,
+    {
+        let (sent_tokens, _) = self.multicast_tokens(tokens);
+
+        let payload_bytes = sent_tokens
+            .iter()
+            .map(|token| {
+                let message = token_message(message, token);
+
+                // The same payloads as `send_with`.
+                match self.pipeline {
+                    SendPipeline::Direct => message.payload().unwrap().len(),
+                    SendPipeline::ValidateThenSend => {
+                        let serialized = message.message_payload().unwrap();
+                        envelope(true, &serialized).len() + envelope(false, &serialized).len()
+                    }
+                }
+            })
+            .sum();
+
+        let requests = match self.pipeline {
+            SendPipeline::Direct => sent_tokens.len(),
+            SendPipeline::ValidateThenSend => 2 * sent_tokens.len(),
+        };
+
+        let failure_rate = expected_failure_rate.clamp(0.0, 1.0);
+        let retries_per_request: f64 = (1..=self.retry_policy.max_retries())
+            .map(|retry| failure_rate.powi(retry as i32))
+            .sum();
+
+        CampaignEstimate {
+            tokens: sent_tokens.len(),
+            requests,
+            batches: (sent_tokens.len() + self.multicast_batch_size - 1) / self.multicast_batch_size,
+            payload_bytes,
+            expected_retries: requests as f64 * retries_per_request,
+        }
+    }
+
+    /// The tokens `send_multi` sends to, and when deduplicating them, the
+    /// index in these of each of the `tokens`.
+    fn multicast_tokens<'t, T>(&self, tokens: &'t [T]) -> (Vec<&'t str>, Option<Vec<usize>>)
+    where
+        T: AsRef<str>,
+    {
+        if !self.dedupe_tokens {
+            return (tokens.iter().map(AsRef::as_ref).collect(), None);
+        }
+
+        let mut unique_tokens = Vec::new();
+        let mut unique_indexes = HashMap::new();
+
+        let indexes = tokens
+            .iter()
+            .map(|token| {
+                *unique_indexes.entry(token.as_ref()).or_insert_with(|| {
+                    unique_tokens.push(token.as_ref());
+                    unique_tokens.len() - 1
+                })
+            })
+            .collect();
+
+        (unique_tokens, Some(indexes))
+    }
+
+    /// Send `message` to each of the `tokens`, for `send_multi`.
+    async fn send_batches<T>(&self, message: &FCMRequest<'_>, tokens: &[T]) -> Vec<MulticastResult>
+    where
+        T: AsRef<str>,
+    {
+        let mut results = Vec::with_capacity(tokens.len());
+        let mut batch_error: Option<FcmError> = None;
+
+        for batch in tokens.chunks(self.multicast_batch_size) {
+            if let Some(ref error) = batch_error {
+                results.extend(batch.iter().map(|token| MulticastResult {
+                    token: token.as_ref().to_string(),
+                    result: Err(error.clone()),
+                }));
+
+                continue;
+            }
+
+            let batch_results: Vec<_> = stream::iter(batch)
+                .map(|token| {
+                    let message = token_message(message, token.as_ref());
+
+                    async move {
+                        MulticastResult {
+                            token: token.as_ref().to_string(),
+                            result: self.send(message).await,
+                        }
+                    }
+                })
+                .buffered(self.multicast_concurrency)
+                .collect()
+                .await;
+
+            batch_error = failed_batch_error(&batch_results);
+            results.extend(batch_results);
+        }
+
+        results
+    }
+
+    /// Ask FCM to validate a `FCMRequest` without sending it, whatever its
+    /// `validate_only` parameter. The returned `ValidatedRequest` can then be
+    /// sent with `send_validated`, without serializing the message again.
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fcm_http1::FcmError> {
+    /// use fcm_http1::{Client, FCMRequestBuilder};
+    ///
+    /// let client = Client::new();
+    /// let builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+    ///
+    /// let validated = client.validate(builder.finalize()).await?;
+    /// let response = client.send_validated(&validated).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn validate<'a>(&self, message: FCMRequest<'a>) -> Result<ValidatedRequest<'a>, FcmError> {
+        self.validate_with(message, &[]).await
+    }
+
+    async fn validate_with<'a>(
+        &self,
+        message: FCMRequest<'a>,
+        headers: &[(HeaderName, HeaderValue)],
+    ) -> Result<ValidatedRequest<'a>, FcmError> {
+        let serialized = message.message_payload().unwrap();
+
+        let response = self
+            .post(message.api_key, message.project, envelope(true, &serialized), headers)
+            .await?;
+
+        Ok(ValidatedRequest {
+            api_key: message.api_key,
+            project: message.project,
+            message: serialized,
+            response,
+        })
+    }
+
+    /// Send a request previously validated with `validate`, reusing its
+    /// serialized message.
+    pub async fn send_validated(&self, validated: &ValidatedRequest<'_>) -> Result<FcmResponse, FcmError> {
+        self.post(
+            validated.api_key,
+            validated.project,
+            envelope(false, &validated.message),
+            &[],
+        )
+        .await
+    }
+
+    async fn post(
+        &self,
+        api_key: &str,
+        project: &str,
+        payload: Vec<u8>,
+        headers: &[(HeaderName, HeaderValue)],
+    ) -> Result<FcmResponse, FcmError> {
+        let url = self.send_url(project)?;
+
+        let _permit = match self.inflight_budget {
+            Some(ref budget) => Some(budget.acquire(payload.len()).await),
+            None => None,
+        };
+
+        let mut request = self.http_client.post(url);
+
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+
+        let request = request
+            .header(CONTENT_TYPE, "application/json; charset=UTF-8")
             .header(CONTENT_LENGTH, format!("{}", payload.len() as u64).as_bytes())
-            .header(AUTHORIZATION, format!("Bearer {}", message.api_key).as_bytes())
+            .header(AUTHORIZATION, format!("Bearer {}", api_key).as_bytes())
             .body(Body::from(payload))
             .build()?;
-        let response = self.http_client.execute(request).await?;
+        let (response, downgraded_to_http1) = self.execute(request).await?;
 
         let response_status = response.status();
 
@@ -54,8 +917,28 @@ impl Client {
             .and_then(|ra| ra.parse::<RetryAfter>().ok());
 
         match response_status {
-            StatusCode::OK => {
-                let fcm_response: FcmResponse = response.json().await.unwrap();
+            status if status.is_success() => {
+                let content_type = response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|ct| ct.to_str().ok())
+                    .map(str::to_owned);
+
+                let apns_unique_id = response
+                    .headers()
+                    .get(APNS_UNIQUE_ID)
+                    .and_then(|id| id.to_str().ok())
+                    .map(str::to_owned);
+
+                let body = response.bytes().await?;
+                let mut fcm_response = match FcmResponse::from_success_body(content_type, &body) {
+                    Err(response::FcmError::EmptySuccessBody) if self.retry_empty_success_body => {
+                        return Err(response::FcmError::ServerError(retry_after));
+                    }
+                    result => result?,
+                };
+                fcm_response.apns_unique_id = apns_unique_id;
+                fcm_response.downgraded_to_http1 = downgraded_to_http1;
 
                 match fcm_response.error {
                     Some(ErrorReason::Unavailable) => Err(response::FcmError::ServerError(retry_after)),
@@ -63,10 +946,64 @@ impl Client {
                     _ => Ok(fcm_response),
                 }
             }
-            StatusCode::UNAUTHORIZED => Err(response::FcmError::Unauthorized),
-            StatusCode::BAD_REQUEST => Err(response::FcmError::InvalidMessage("Bad Request".to_string())),
-            status if status.is_server_error() => Err(response::FcmError::ServerError(retry_after)),
-            _ => Err(response::FcmError::InvalidMessage("Unknown Error".to_string())),
+            // The error code of the body is more precise than the status, which
+            // is only used when FCM gave no code.
+            status => match FcmErrorCode::from_error_body(&response.bytes().await?) {
+                Some(code) => Err(response::FcmError::Rejected { code, retry_after }),
+                None if status == StatusCode::UNAUTHORIZED => Err(response::FcmError::Unauthorized),
+                None if status == StatusCode::BAD_REQUEST => {
+                    Err(response::FcmError::InvalidMessage("Bad Request".to_string()))
+                }
+                None if status.is_server_error() => Err(response::FcmError::ServerError(retry_after)),
+                None => Err(response::FcmError::InvalidMessage("Unknown Error".to_string())),
+            },
+        }
+    }
+
+    /// Execute `request`, sending it again once over HTTP/1.1 when it fails
+    /// over HTTP/2, and then only using HTTP/1.1. Also tells whether the
+    /// request was sent again.
+    async fn execute(&self, request: reqwest::Request) -> Result<(reqwest::Response, bool), reqwest::Error> {
+        let fallback = match self.http1_fallback {
+            Some(ref fallback) if fallback.downgraded.load(Ordering::Relaxed) => {
+                return Ok((fallback.http_client.execute(request).await?, false));
+            }
+            Some(ref fallback) => fallback,
+            None => return Ok((self.http_client.execute(request).await?, false)),
+        };
+
+        // The body is in memory, so the request can always be cloned.
+        let retry = request.try_clone();
+
+        match (self.http_client.execute(request).await, retry) {
+            (Err(ref error), Some(retry)) if is_http2_failure(error) => {
+                fallback.downgraded.store(true, Ordering::Relaxed);
+                fallback.downgrades.fetch_add(1, Ordering::Relaxed);
+
+                Ok((fallback.http_client.execute(retry).await?, true))
+            }
+            (response, _) => Ok((response?, false)),
         }
     }
 }
+
+/// The copy of `message` sent to `token` by `send_multi`.
+fn token_message<'a>(message: &FCMRequest<'a>, token: &str) -> FCMRequest<'a> {
+    let mut builder = message.to_builder();
+    builder.token(token.to_string());
+    builder.finalize()
+}
+
+/// The error of a `send_multi` batch whose messages all failed with the same
+/// error, which is not about their token.
+fn failed_batch_error(results: &[MulticastResult]) -> Option<FcmError> {
+    let error = match results.first()?.result {
+        Err(ref error @ (FcmError::Unauthorized | FcmError::InvalidProjectId(_))) => error,
+        _ => return None,
+    };
+
+    results
+        .iter()
+        .all(|result| result.result.as_ref().err() == Some(error))
+        .then(|| error.clone())
+}
@@ -1,6 +1,7 @@
 pub use chrono::{DateTime, Duration, FixedOffset};
 use serde::Deserialize;
-use std::{error::Error, fmt, str::FromStr};
+use serde_json::Value;
+use std::{convert::Infallible, error::Error, fmt, str::FromStr};
 
 /// A description of what went wrong with the push notification.
 /// Referred from [Firebase documentation](https://firebase.google.com/docs/cloud-messaging/http-server-ref#table9)
@@ -94,7 +95,163 @@ pub enum ErrorReason {
     InvalidApnsCredential,
 }
 
-#[derive(Deserialize, Debug)]
+/// The error codes of the FCM HTTP v1 API.
+/// Referred from [Firebase documentation](https://firebase.google.com/docs/reference/fcm/rest/v1/ErrorCode)
+///
+/// The codes parse from and display as their exact wire strings, e.g.
+/// `UNREGISTERED`. Codes unknown to this crate parse to `Unknown`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum FcmErrorCode {
+    /// No more information is available about this error.
+    UnspecifiedError,
+
+    /// Request parameters were invalid, e.g. an invalid registration token or
+    /// a payload exceeding the size limits.
+    InvalidArgument,
+
+    /// The app instance was unregistered from FCM. This usually means that the
+    /// token used is no longer valid and a new one must be used.
+    Unregistered,
+
+    /// The authenticated sender ID is different from the sender ID for the
+    /// registration token.
+    SenderIdMismatch,
+
+    /// Sending limit exceeded for the message target.
+    QuotaExceeded,
+
+    /// The server is overloaded.
+    Unavailable,
+
+    /// An unknown internal error occurred.
+    Internal,
+
+    /// APNs certificate or web push auth key was invalid or missing.
+    ThirdPartyAuthError,
+
+    /// An error code unknown to this crate.
+    Unknown(String),
+}
+
+impl FcmErrorCode {
+    /// All the error codes known to this crate.
+    pub const KNOWN: [FcmErrorCode; 8] = [
+        FcmErrorCode::UnspecifiedError,
+        FcmErrorCode::InvalidArgument,
+        FcmErrorCode::Unregistered,
+        FcmErrorCode::SenderIdMismatch,
+        FcmErrorCode::QuotaExceeded,
+        FcmErrorCode::Unavailable,
+        FcmErrorCode::Internal,
+        FcmErrorCode::ThirdPartyAuthError,
+    ];
+
+    /// The wire string of the error code, e.g. `UNREGISTERED`. All unknown
+    /// codes map to `UNKNOWN`, so the result can be used as a metrics label
+    /// without allocating nor growing its cardinality. Use `Display` to get
+    /// the original string of an unknown code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FcmErrorCode::UnspecifiedError => "UNSPECIFIED_ERROR",
+            FcmErrorCode::InvalidArgument => "INVALID_ARGUMENT",
+            FcmErrorCode::Unregistered => "UNREGISTERED",
+            FcmErrorCode::SenderIdMismatch => "SENDER_ID_MISMATCH",
+            FcmErrorCode::QuotaExceeded => "QUOTA_EXCEEDED",
+            FcmErrorCode::Unavailable => "UNAVAILABLE",
+            FcmErrorCode::Internal => "INTERNAL",
+            FcmErrorCode::ThirdPartyAuthError => "THIRD_PARTY_AUTH_ERROR",
+            FcmErrorCode::Unknown(_) => "UNKNOWN",
+        }
+    }
+
+    /// The support bucket of the error code.
+    pub fn triage(&self) -> Triage {
+        match self {
+            FcmErrorCode::Unregistered => Triage::AppUninstalled,
+            FcmErrorCode::InvalidArgument => Triage::PayloadBug,
+            FcmErrorCode::SenderIdMismatch
+            | FcmErrorCode::QuotaExceeded
+            | FcmErrorCode::ThirdPartyAuthError
+            | FcmErrorCode::UnspecifiedError
+            | FcmErrorCode::Unknown(_) => Triage::Operations,
+            FcmErrorCode::Unavailable | FcmErrorCode::Internal => Triage::Transient,
+        }
+    }
+}
+
+/// The buckets delivery complaints are triaged into, with the action they
+/// call for.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Triage {
+    /// The app was uninstalled, or its token expired: the token must be
+    /// removed.
+    AppUninstalled,
+
+    /// The user disabled the notifications of the app. FCM accepts these
+    /// messages as usual and gives no signal about it, so no error is triaged
+    /// here: it is only a possible explanation of messages accepted but never
+    /// displayed.
+    NotificationsDisabled,
+
+    /// The message is invalid and needs to be fixed before sending it again.
+    PayloadBug,
+
+    /// The credentials, the project configuration or the quotas need to be
+    /// looked at.
+    Operations,
+
+    /// The error is temporary, the message can be retried as is.
+    Transient,
+}
+
+impl Triage {
+    /// A human hint about what to do with the errors of this bucket.
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            Triage::AppUninstalled => "the app was uninstalled or its token expired, remove the token",
+            Triage::NotificationsDisabled => "check the notifications of the app are enabled on the device",
+            Triage::PayloadBug => "the message is invalid, fix it before sending it again",
+            Triage::Operations => "check the credentials, the project configuration and the sending quotas",
+            Triage::Transient => "temporary failure, retry later with an exponential back-off",
+        }
+    }
+}
+
+impl FcmErrorCode {
+    /// Find the error code in the body of a v1 error response, which looks
+    /// like `{"error": {"details": [{"errorCode": "UNREGISTERED", ...}]}}`.
+    pub(crate) fn from_error_body(body: &[u8]) -> Option<FcmErrorCode> {
+        let body: Value = serde_json::from_slice(body).ok()?;
+
+        body["error"]["details"]
+            .as_array()?
+            .iter()
+            .find_map(|detail| detail["errorCode"].as_str())
+            .map(|code| code.parse().unwrap())
+    }
+}
+
+impl FromStr for FcmErrorCode {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(FcmErrorCode::KNOWN
+            .into_iter()
+            .find(|code| code.as_str() == s)
+            .unwrap_or_else(|| FcmErrorCode::Unknown(s.to_string())))
+    }
+}
+
+impl fmt::Display for FcmErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FcmErrorCode::Unknown(ref code) => f.write_str(code),
+            code => f.write_str(code.as_str()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct FcmResponse {
     pub message_id: Option<u64>,
     pub error: Option<ErrorReason>,
@@ -103,9 +260,102 @@ pub struct FcmResponse {
     pub failure: Option<u64>,
     pub canonical_ids: Option<u64>,
     pub results: Option<Vec<MessageResult>>,
+
+    /// The `apns-unique-id` header of the response, identifying the
+    /// notification in Apple's delivery logs. Only set when the server
+    /// returned one.
+    #[serde(skip)]
+    pub apns_unique_id: Option<String>,
+
+    /// Whether the request failed over HTTP/2 and was sent again over
+    /// HTTP/1.1, which the client then keeps using, see
+    /// `Client::is_downgraded_to_http1`.
+    #[serde(skip)]
+    pub downgraded_to_http1: bool,
+}
+
+impl FcmResponse {
+    /// Parse the body of a successful (2xx) response. Empty and non-JSON
+    /// bodies are reported as their own errors instead of as a FCM rejection.
+    ///
+    /// The body is parsed when its content type is JSON, e.g.
+    /// `application/json;charset=UTF-8` in any case, or when the content type
+    /// is missing and the body looks like a JSON object.
+    pub(crate) fn from_success_body(content_type: Option<String>, body: &[u8]) -> Result<FcmResponse, FcmError> {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Err(FcmError::EmptySuccessBody);
+        }
+
+        let parse = match content_type {
+            Some(ref content_type) => is_json_content_type(content_type),
+            None => body.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{'),
+        };
+
+        let fcm_response = if parse { serde_json::from_slice(body).ok() } else { None };
+
+        fcm_response.ok_or_else(|| FcmError::UnexpectedContentType {
+            content_type,
+            snippet: String::from_utf8_lossy(body).chars().take(SNIPPET_LENGTH).collect(),
+        })
+    }
+}
+
+/// Check the media type of `content_type` is JSON: `json` or a `+json`
+/// suffix, in any case, whatever its parameters.
+fn is_json_content_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+
+    match media_type.split_once('/') {
+        Some((_, subtype)) => {
+            let subtype = subtype.to_ascii_lowercase();
+            subtype == "json" || subtype.ends_with("+json")
+        }
+        None => false,
+    }
+}
+
+/// The results of `Client::send_multi`, one per token, in the order of the
+/// tokens.
+#[derive(Debug)]
+pub struct MulticastResponse {
+    pub results: Vec<MulticastResult>,
+}
+
+/// The result of sending a message to one of the tokens of
+/// `Client::send_multi`.
+#[derive(Debug, Clone)]
+pub struct MulticastResult {
+    pub token: String,
+    pub result: Result<FcmResponse, FcmError>,
+}
+
+impl MulticastResponse {
+    /// How many messages were sent.
+    pub fn success_count(&self) -> usize {
+        self.results.iter().filter(|result| result.result.is_ok()).count()
+    }
+
+    /// How many messages could not be sent.
+    pub fn failure_count(&self) -> usize {
+        self.results.len() - self.success_count()
+    }
+
+    /// The tokens FCM rejected as `UNREGISTERED`, which should be removed.
+    pub fn unregistered_tokens(&self) -> Vec<&str> {
+        self.results
+            .iter()
+            .filter(|result| {
+                result.result.as_ref().err().and_then(FcmError::error_code) == Some(&FcmErrorCode::Unregistered)
+            })
+            .map(|result| result.token.as_str())
+            .collect()
+    }
 }
 
-#[derive(Deserialize, Debug)]
+/// How many characters of an unexpected response body are kept in the error.
+const SNIPPET_LENGTH: usize = 64;
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct MessageResult {
     pub message_id: Option<String>,
     pub registration_id: Option<String>,
@@ -114,7 +364,7 @@ pub struct MessageResult {
 
 /// Fatal errors. Referred from [Firebase
 /// documentation](https://firebase.google.com/docs/cloud-messaging/http-server-ref#table9)
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum FcmError {
     /// The sender account used to send a message couldn't be authenticated. Possible causes are:
     ///
@@ -147,6 +397,73 @@ pub enum FcmError {
     ///
     /// Senders that cause problems risk being blacklisted.
     ServerError(Option<RetryAfter>),
+
+    /// The project is not a valid Google Cloud project id. The request is not
+    /// sent, as the project is part of the request path.
+    InvalidProjectId(String),
+
+    /// The server answered with a success status but an empty body. This is
+    /// usually caused by a proxy or middlebox between the client and FCM, so
+    /// it is not known whether the message was accepted. Not retryable, unless
+    /// the client is built with `ClientBuilder::retry_empty_success_body`,
+    /// which reports it as a `FcmError::ServerError` instead.
+    EmptySuccessBody,
+
+    /// The server answered with a success status but a body that is not a
+    /// JSON FCM response, usually an error page from a proxy or middlebox.
+    /// Like for `FcmError::EmptySuccessBody`, it is not known whether the
+    /// message was accepted, so it is not retryable.
+    UnexpectedContentType {
+        /// The `Content-Type` header of the response, if any.
+        content_type: Option<String>,
+        /// The start of the response body.
+        snippet: String,
+    },
+
+    /// FCM rejected the message with the given error code, e.g.
+    /// `FcmErrorCode::Unregistered` for a token which is no longer valid.
+    Rejected {
+        code: FcmErrorCode,
+        /// The `Retry-After` of the response, e.g. for
+        /// `FcmErrorCode::QuotaExceeded`.
+        retry_after: Option<RetryAfter>,
+    },
+}
+
+impl FcmError {
+    /// The support bucket of the error.
+    pub fn triage(&self) -> Triage {
+        match self {
+            FcmError::Unauthorized
+            | FcmError::InvalidProjectId(_)
+            | FcmError::EmptySuccessBody
+            | FcmError::UnexpectedContentType { .. } => Triage::Operations,
+            FcmError::InvalidMessage(_) => Triage::PayloadBug,
+            FcmError::ServerError(_) => Triage::Transient,
+            FcmError::Rejected { ref code, .. } => code.triage(),
+        }
+    }
+
+    /// How long the server asked to wait before retrying, if it did.
+    pub fn retry_after(&self) -> Option<&RetryAfter> {
+        match self {
+            FcmError::ServerError(ref retry_after) | FcmError::Rejected { ref retry_after, .. } => retry_after.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The FCM error code of the error, if FCM gave one.
+    pub fn error_code(&self) -> Option<&FcmErrorCode> {
+        match self {
+            FcmError::Rejected { ref code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    /// A human hint about what to do with the error, see `Triage::suggestion`.
+    pub fn suggestion(&self) -> &'static str {
+        self.triage().suggestion()
+    }
 }
 
 impl Error for FcmError {}
@@ -157,6 +474,17 @@ impl fmt::Display for FcmError {
             FcmError::Unauthorized => write!(f, "authorization header missing or with invalid syntax in HTTP request"),
             FcmError::InvalidMessage(ref s) => write!(f, "invalid message {}", s),
             FcmError::ServerError(_) => write!(f, "the server couldn't process the request"),
+            FcmError::InvalidProjectId(ref project) => write!(f, "invalid project id {:?}", project),
+            FcmError::EmptySuccessBody => write!(f, "the server answered with an empty success response"),
+            FcmError::UnexpectedContentType {
+                content_type: Some(ref content_type),
+                ref snippet,
+            } => write!(f, "unexpected success response of type {}: {}", content_type, snippet),
+            FcmError::UnexpectedContentType {
+                content_type: None,
+                ref snippet,
+            } => write!(f, "unexpected success response: {}", snippet),
+            FcmError::Rejected { ref code, .. } => write!(f, "the message was rejected with {}", code),
         }
     }
 }
@@ -167,7 +495,7 @@ impl From<reqwest::Error> for FcmError {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum RetryAfter {
     /// Amount of time to wait until retrying the message is allowed.
     Delay(Duration),
@@ -224,12 +552,209 @@ mod tests {
             let response_string = serde_json::to_string(&response_data).unwrap();
             let fcm_response: FcmResponse = serde_json::from_str(&response_string).unwrap();
 
-            assert_eq!(Some(error_enum.clone()), fcm_response.results.unwrap()[0].error,);
+            assert_eq!(Some(error_enum), fcm_response.results.unwrap()[0].error,);
 
             assert_eq!(Some(error_enum), fcm_response.error,)
         }
     }
 
+    #[test]
+    fn test_error_codes_round_trip() {
+        let codes = vec![
+            ("UNSPECIFIED_ERROR", FcmErrorCode::UnspecifiedError),
+            ("INVALID_ARGUMENT", FcmErrorCode::InvalidArgument),
+            ("UNREGISTERED", FcmErrorCode::Unregistered),
+            ("SENDER_ID_MISMATCH", FcmErrorCode::SenderIdMismatch),
+            ("QUOTA_EXCEEDED", FcmErrorCode::QuotaExceeded),
+            ("UNAVAILABLE", FcmErrorCode::Unavailable),
+            ("INTERNAL", FcmErrorCode::Internal),
+            ("THIRD_PARTY_AUTH_ERROR", FcmErrorCode::ThirdPartyAuthError),
+        ];
+
+        assert_eq!(FcmErrorCode::KNOWN.len(), codes.len());
+
+        for (code_str, code_enum) in codes.into_iter() {
+            assert_eq!(code_enum, code_str.parse().unwrap());
+            assert_eq!(code_str, code_enum.as_str());
+            assert_eq!(code_str, code_enum.to_string());
+        }
+    }
+
+    #[test]
+    fn test_unknown_error_code_round_trip() {
+        let code: FcmErrorCode = "APNS_AUTH_ERROR".parse().unwrap();
+
+        assert_eq!(FcmErrorCode::Unknown("APNS_AUTH_ERROR".to_string()), code);
+        assert_eq!("UNKNOWN", code.as_str());
+        assert_eq!("APNS_AUTH_ERROR", code.to_string());
+    }
+
+    #[test]
+    fn test_error_codes_triage() {
+        let buckets = vec![
+            (FcmErrorCode::UnspecifiedError, Triage::Operations),
+            (FcmErrorCode::InvalidArgument, Triage::PayloadBug),
+            (FcmErrorCode::Unregistered, Triage::AppUninstalled),
+            (FcmErrorCode::SenderIdMismatch, Triage::Operations),
+            (FcmErrorCode::QuotaExceeded, Triage::Operations),
+            (FcmErrorCode::Unavailable, Triage::Transient),
+            (FcmErrorCode::Internal, Triage::Transient),
+            (FcmErrorCode::ThirdPartyAuthError, Triage::Operations),
+        ];
+
+        assert_eq!(FcmErrorCode::KNOWN.len(), buckets.len());
+
+        for (code, bucket) in buckets.into_iter() {
+            assert_eq!(bucket, code.triage());
+        }
+
+        assert_eq!(
+            Triage::Operations,
+            FcmErrorCode::Unknown("NEW_CODE".to_string()).triage()
+        );
+    }
+
+    #[test]
+    fn test_errors_triage() {
+        let buckets = vec![
+            (FcmError::Unauthorized, Triage::Operations),
+            (FcmError::InvalidMessage("Bad Request".to_string()), Triage::PayloadBug),
+            (FcmError::ServerError(None), Triage::Transient),
+            (FcmError::InvalidProjectId("project".to_string()), Triage::Operations),
+            (FcmError::EmptySuccessBody, Triage::Operations),
+            (
+                FcmError::UnexpectedContentType {
+                    content_type: None,
+                    snippet: String::new(),
+                },
+                Triage::Operations,
+            ),
+            (
+                FcmError::Rejected {
+                    code: FcmErrorCode::Unregistered,
+                    retry_after: None,
+                },
+                Triage::AppUninstalled,
+            ),
+        ];
+
+        for (error, bucket) in buckets.into_iter() {
+            assert_eq!(bucket, error.triage());
+            assert_eq!(bucket.suggestion(), error.suggestion());
+        }
+    }
+
+    #[test]
+    fn test_error_codes_are_case_sensitive() {
+        assert_eq!(
+            FcmErrorCode::Unknown("unregistered".to_string()),
+            "unregistered".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_error_code_from_error_body() {
+        let body = json!({
+            "error": {
+                "code": 404,
+                "message": "Requested entity was not found.",
+                "status": "NOT_FOUND",
+                "details": [
+                    {"@type": "type.googleapis.com/google.rpc.BadRequest"},
+                    {"@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError", "errorCode": "UNREGISTERED"},
+                ],
+            },
+        });
+
+        assert_eq!(
+            Some(FcmErrorCode::Unregistered),
+            FcmErrorCode::from_error_body(body.to_string().as_bytes())
+        );
+        assert_eq!(None, FcmErrorCode::from_error_body(br#"{"error": {"code": 404}}"#));
+        assert_eq!(None, FcmErrorCode::from_error_body(b"Not Found"));
+    }
+
+    #[test]
+    fn test_empty_success_body() {
+        assert_eq!(
+            Some(FcmError::EmptySuccessBody),
+            FcmResponse::from_success_body(Some("application/json".to_string()), b"").err(),
+        );
+
+        assert_eq!(
+            Some(FcmError::EmptySuccessBody),
+            FcmResponse::from_success_body(None, b" \r\n").err(),
+        );
+    }
+
+    #[test]
+    fn test_non_json_success_body() {
+        let body = b"<html><body>Service temporarily unavailable</body></html>";
+
+        assert_eq!(
+            Some(FcmError::UnexpectedContentType {
+                content_type: Some("text/html".to_string()),
+                snippet: "<html><body>Service temporarily unavailable</body></html>".to_string(),
+            }),
+            FcmResponse::from_success_body(Some("text/html".to_string()), body).err(),
+        );
+    }
+
+    #[test]
+    fn test_non_json_success_body_snippet_is_truncated() {
+        let body = "x".repeat(1000);
+
+        match FcmResponse::from_success_body(None, body.as_bytes()) {
+            Err(FcmError::UnexpectedContentType { content_type, snippet }) => {
+                assert_eq!(None, content_type);
+                assert_eq!(SNIPPET_LENGTH, snippet.len());
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_content_types() {
+        let body = br#"{"message_id": 42}"#;
+
+        for content_type in [
+            "application/json",
+            "application/json;charset=UTF-8",
+            "Application/JSON; charset=utf-8",
+            " application/json ; charset=\"UTF-8\"",
+            "text/json",
+            "application/problem+json",
+        ] {
+            let fcm_response = FcmResponse::from_success_body(Some(content_type.to_string()), body);
+
+            assert_eq!(Some(42), fcm_response.unwrap().message_id, "{}", content_type);
+        }
+
+        for content_type in ["text/plain", "application/jsonp", "json", ""] {
+            assert!(
+                FcmResponse::from_success_body(Some(content_type.to_string()), body).is_err(),
+                "{}",
+                content_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_success_body_without_content_type() {
+        let fcm_response = FcmResponse::from_success_body(None, b"  {\"message_id\": 42}").unwrap();
+
+        assert_eq!(Some(42), fcm_response.message_id);
+        assert!(FcmResponse::from_success_body(None, b"42").is_err());
+    }
+
+    #[test]
+    fn test_json_success_body() {
+        let fcm_response =
+            FcmResponse::from_success_body(Some("application/json".to_string()), br#"{"message_id": 42}"#).unwrap();
+
+        assert_eq!(Some(42), fcm_response.message_id);
+    }
+
     #[test]
     fn test_retry_after_from_seconds() {
         assert_eq!(RetryAfter::Delay(Duration::seconds(420)), "420".parse().unwrap());
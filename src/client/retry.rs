@@ -0,0 +1,408 @@
+use crate::wire_time::{deserialize_duration, serialize_duration};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{fmt, time::Duration};
+
+/// How the delays between retries are randomized.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Jitter {
+    /// Wait the exact delay of the policy.
+    None,
+
+    /// Wait a random delay between zero and the delay of the policy, so
+    /// failed senders do not all retry at the same time.
+    Full,
+}
+
+/// How many times and how long to wait before retrying a message which failed
+/// with a `FcmError::ServerError`. The delays grow exponentially from
+/// `base_delay`, up to `max_delay`.
+///
+/// Use one of the presets rather than choosing the numbers. The client does
+/// not retry on its own: `Client::send` and `Client::send_multi` send each
+/// message once. The policy of a client only affects `Client::estimate`, and
+/// is held for the retry loop of the caller: `RetryPolicy::standard()` unless
+/// `ClientBuilder::retry_policy` says otherwise.
+///
+/// A policy serializes with stable field names, so it can be handed from a
+/// service to another. The delays serialize as protobuf duration strings,
+/// e.g. `"0.500s"`. When deserializing, unknown fields are ignored and missing
+/// ones take the value of the preset the name refers to, or of
+/// `RetryPolicy::standard()` without such a name. The name becomes `custom`
+/// unless it refers to a preset and all the given values are the ones of that
+/// preset.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{ClientBuilder, RetryPolicy};
+///
+/// let mut builder = ClientBuilder::new();
+/// builder.retry_policy(RetryPolicy::aggressive());
+/// let client = builder.build().unwrap();
+///
+/// println!("retrying with the {} policy", client.retry_policy());
+/// ```
+#[derive(Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    name: PolicyName,
+    max_retries: u32,
+    #[serde(serialize_with = "serialize_duration")]
+    base_delay: Duration,
+    #[serde(serialize_with = "serialize_duration")]
+    max_delay: Duration,
+    jitter: Jitter,
+    strict_retry_after: bool,
+}
+
+/// The name of a `RetryPolicy`.
+#[derive(Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(transparent)]
+struct PolicyName(&'static str);
+
+fn custom_name() -> PolicyName {
+    PolicyName("custom")
+}
+
+/// The fields of a serialized `RetryPolicy`, any of which can be missing.
+#[derive(Deserialize)]
+struct PolicyFields {
+    name: Option<String>,
+    max_retries: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_some_duration")]
+    base_delay: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_some_duration")]
+    max_delay: Option<Duration>,
+    jitter: Option<Jitter>,
+    strict_retry_after: Option<bool>,
+}
+
+fn deserialize_some_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_duration(deserializer).map(Some)
+}
+
+impl<'de> Deserialize<'de> for RetryPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let fields = PolicyFields::deserialize(deserializer)?;
+        let preset = fields.name.as_deref().and_then(RetryPolicy::preset);
+        let defaults = preset.unwrap_or_else(RetryPolicy::standard);
+
+        let policy = RetryPolicy {
+            name: defaults.name,
+            max_retries: fields.max_retries.unwrap_or(defaults.max_retries),
+            base_delay: fields.base_delay.unwrap_or(defaults.base_delay),
+            max_delay: fields.max_delay.unwrap_or(defaults.max_delay),
+            jitter: fields.jitter.unwrap_or(defaults.jitter),
+            strict_retry_after: fields.strict_retry_after.unwrap_or(defaults.strict_retry_after),
+        };
+
+        if preset == Some(policy) {
+            Ok(policy)
+        } else {
+            Ok(RetryPolicy {
+                name: custom_name(),
+                ..policy
+            })
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl RetryPolicy {
+    /// For interactive paths, where a user is waiting for the message: 2
+    /// retries, from 50ms up to 1s, with full jitter.
+    pub fn aggressive() -> RetryPolicy {
+        RetryPolicy {
+            name: PolicyName("aggressive"),
+            max_retries: 2,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(1),
+            jitter: Jitter::Full,
+            strict_retry_after: false,
+        }
+    }
+
+    /// For most sends: 5 retries, from 500ms up to 30s, with full jitter.
+    pub fn standard() -> RetryPolicy {
+        RetryPolicy {
+            name: PolicyName("standard"),
+            max_retries: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: Jitter::Full,
+            strict_retry_after: false,
+        }
+    }
+
+    /// For campaigns, where throughput matters more than latency: 8 retries,
+    /// from 1s up to 5 minutes, with full jitter, and always waiting at least
+    /// the `Retry-After` of the response.
+    pub fn campaign() -> RetryPolicy {
+        RetryPolicy {
+            name: PolicyName("campaign"),
+            max_retries: 8,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(300),
+            jitter: Jitter::Full,
+            strict_retry_after: true,
+        }
+    }
+
+    /// A custom policy, which does not honor `Retry-After` strictly.
+    pub fn new(max_retries: u32, base_delay: Duration, max_delay: Duration, jitter: Jitter) -> RetryPolicy {
+        RetryPolicy {
+            name: PolicyName("custom"),
+            max_retries,
+            base_delay,
+            max_delay,
+            jitter,
+            strict_retry_after: false,
+        }
+    }
+
+    /// The preset called `name`, if any.
+    fn preset(name: &str) -> Option<RetryPolicy> {
+        [
+            RetryPolicy::aggressive(),
+            RetryPolicy::standard(),
+            RetryPolicy::campaign(),
+        ]
+        .into_iter()
+        .find(|preset| preset.name.0 == name)
+    }
+
+    /// The name of the preset, or `custom`.
+    pub fn name(&self) -> &'static str {
+        self.name.0
+    }
+
+    /// How many times a message is retried after its first attempt.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// The delay before the first retry.
+    pub fn base_delay(&self) -> Duration {
+        self.base_delay
+    }
+
+    /// The longest delay between two retries.
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    /// How the delays are randomized.
+    pub fn jitter(&self) -> Jitter {
+        self.jitter
+    }
+
+    /// Whether the `Retry-After` of a response is always waited for, even when
+    /// longer than `max_delay`.
+    pub fn strict_retry_after(&self) -> bool {
+        self.strict_retry_after
+    }
+
+    /// The delay before the retry number `retry` (starting at zero), before
+    /// jitter, or `None` once all the retries are used. `retry_after` is the
+    /// delay the server asked for, if any.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if retry >= self.max_retries {
+            return None;
+        }
+
+        let backoff = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+
+        Some(match retry_after {
+            Some(retry_after) if self.strict_retry_after => retry_after.max(backoff),
+            Some(retry_after) => retry_after.max(backoff).min(self.max_delay),
+            None => backoff,
+        })
+    }
+}
+
+impl fmt::Display for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} retries, {:?} base delay, {:?} max delay",
+            self.name.0, self.max_retries, self.base_delay, self.max_delay
+        )?;
+
+        if self.jitter == Jitter::Full {
+            write!(f, ", full jitter")?;
+        }
+
+        if self.strict_retry_after {
+            write!(f, ", strict Retry-After")?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_presets() {
+        let presets = vec![
+            (RetryPolicy::aggressive(), 2, 50, 1_000, false),
+            (RetryPolicy::standard(), 5, 500, 30_000, false),
+            (RetryPolicy::campaign(), 8, 1_000, 300_000, true),
+        ];
+
+        for (policy, max_retries, base_delay, max_delay, strict_retry_after) in presets.into_iter() {
+            assert_eq!(max_retries, policy.max_retries());
+            assert_eq!(Duration::from_millis(base_delay), policy.base_delay());
+            assert_eq!(Duration::from_millis(max_delay), policy.max_delay());
+            assert_eq!(Jitter::Full, policy.jitter());
+            assert_eq!(strict_retry_after, policy.strict_retry_after());
+        }
+
+        assert_eq!(RetryPolicy::standard(), RetryPolicy::default());
+    }
+
+    #[test]
+    fn test_delays() {
+        let policy = RetryPolicy::standard();
+
+        let delays: Vec<_> = (0..6).map(|retry| policy.delay(retry, None)).collect();
+
+        assert_eq!(
+            vec![
+                Some(Duration::from_millis(500)),
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(4)),
+                Some(Duration::from_secs(8)),
+                None,
+            ],
+            delays
+        );
+
+        let policy = RetryPolicy::new(100, Duration::from_secs(1), Duration::from_secs(10), Jitter::None);
+
+        assert_eq!(Some(Duration::from_secs(10)), policy.delay(99, None));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let retry_after = Some(Duration::from_secs(600));
+
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            RetryPolicy::standard().delay(0, retry_after)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(600)),
+            RetryPolicy::campaign().delay(0, retry_after)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(2)),
+            RetryPolicy::campaign().delay(1, Some(Duration::from_secs(1)))
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for policy in [
+            RetryPolicy::aggressive(),
+            RetryPolicy::standard(),
+            RetryPolicy::campaign(),
+            RetryPolicy::new(3, Duration::from_millis(10), Duration::from_secs(2), Jitter::None),
+        ] {
+            let serialized = serde_json::to_string(&policy).unwrap();
+
+            assert_eq!(policy, serde_json::from_str(&serialized).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_deserialize_partial_and_unknown_fields() {
+        let policy: RetryPolicy = serde_json::from_value(json!({
+            "name": "experimental",
+            "max_retries": 3,
+            "deadline": "10s",
+        }))
+        .unwrap();
+
+        assert_eq!("custom", policy.name());
+        assert_eq!(3, policy.max_retries());
+        assert_eq!(RetryPolicy::standard().base_delay(), policy.base_delay());
+        assert_eq!(RetryPolicy::standard().jitter(), policy.jitter());
+
+        let policy: RetryPolicy = serde_json::from_value(json!({"max_retries": 3})).unwrap();
+
+        assert_eq!("custom", policy.name());
+    }
+
+    #[test]
+    fn test_deserialize_overridden_preset() {
+        let policy: RetryPolicy = serde_json::from_value(json!({"name": "standard"})).unwrap();
+
+        assert_eq!(RetryPolicy::standard(), policy);
+
+        let policy: RetryPolicy = serde_json::from_value(json!({"name": "standard", "max_retries": 3})).unwrap();
+
+        assert_eq!("custom", policy.name());
+        assert_eq!(3, policy.max_retries());
+
+        // The missing fields take the values of the named preset.
+        let policy: RetryPolicy = serde_json::from_value(json!({"name": "campaign"})).unwrap();
+
+        assert_eq!(RetryPolicy::campaign(), policy);
+
+        let policy: RetryPolicy =
+            serde_json::from_value(json!({"name": "campaign", "max_retries": 8, "max_delay": "300s"})).unwrap();
+
+        assert_eq!(RetryPolicy::campaign(), policy);
+
+        let policy: RetryPolicy = serde_json::from_value(json!({"name": "campaign", "max_retries": 3})).unwrap();
+
+        assert_eq!("custom", policy.name());
+        assert_eq!(3, policy.max_retries());
+        assert_eq!(RetryPolicy::campaign().base_delay(), policy.base_delay());
+        assert!(policy.strict_retry_after());
+    }
+
+    #[test]
+    fn test_display_and_serialize() {
+        assert_eq!(
+            "standard (5 retries, 500ms base delay, 30s max delay, full jitter)",
+            RetryPolicy::standard().to_string()
+        );
+
+        assert_eq!(
+            "campaign (8 retries, 1s base delay, 300s max delay, full jitter, strict Retry-After)",
+            RetryPolicy::campaign().to_string()
+        );
+
+        assert_eq!(
+            json!({
+                "name": "aggressive",
+                "max_retries": 2,
+                "base_delay": "0.050s",
+                "max_delay": "1s",
+                "jitter": "full",
+                "strict_retry_after": false,
+            }),
+            serde_json::to_value(RetryPolicy::aggressive()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,1279 @@
+use crate::message::tests::MESSAGE_SERIALIZATIONS;
+use crate::{
+    Client, ClientBuilder, ConfigError, FCMRequestBuilder, FcmError, FcmErrorCode, Jitter, Priority, RetryAfter,
+    RetryPolicy, SendOptions, SendPipeline, Triage,
+};
+use serde_json::json;
+use std::cell::Cell;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// A local HTTP server answering each connection with the next of the given
+/// responses, and recording the requests it received.
+struct MockServer {
+    addr: SocketAddr,
+    requests: Receiver<String>,
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+    server_names: Receiver<Option<String>>,
+}
+
+impl MockServer {
+    fn start(responses: Vec<String>) -> MockServer {
+        Self::start_with(responses, None)
+    }
+
+    /// Start a server which only answers a request once released, holding
+    /// the request open until then.
+    fn start_held(responses: Vec<String>) -> (MockServer, Sender<()>) {
+        let (release, released) = channel();
+
+        (Self::start_with(responses, Some(released)), release)
+    }
+
+    fn start_with(responses: Vec<String>, released: Option<Receiver<()>>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, requests) = channel();
+
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                serve(stream, &response, &sender, released.as_ref());
+            }
+        });
+
+        MockServer {
+            addr,
+            requests,
+            #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+            server_names: channel().1,
+        }
+    }
+
+    /// Start a server answering over TLS with the certificate of `fcm.test`
+    /// in `testdata`, signed by `testdata/ca.pem`, and recording the server
+    /// name each connection asked for.
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+    fn start_tls(responses: Vec<String>) -> MockServer {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+        use rustls::{ServerConfig, ServerConnection, StreamOwned};
+        use std::sync::Arc;
+
+        let certificate = CertificateDer::from_pem_slice(include_bytes!("testdata/fcm.test.pem")).unwrap();
+        let key = PrivateKeyDer::from_pem_slice(include_bytes!("testdata/fcm.test.key")).unwrap();
+
+        let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate], key)
+            .unwrap();
+        let config = Arc::new(config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, requests) = channel();
+        let (server_name_sender, server_names) = channel();
+
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut connection = ServerConnection::new(config.clone()).unwrap();
+
+                while connection.is_handshaking() {
+                    connection.complete_io(&mut stream).unwrap();
+                }
+
+                server_name_sender
+                    .send(connection.server_name().map(str::to_owned))
+                    .unwrap();
+                serve(StreamOwned::new(connection, stream), &response, &sender, None);
+            }
+        });
+
+        MockServer {
+            addr,
+            requests,
+            server_names,
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    fn next_request(&self) -> String {
+        self.requests.recv().unwrap()
+    }
+
+    /// The server name the next TLS connection asked for, if any.
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+    fn next_server_name(&self) -> Option<String> {
+        self.server_names.recv().unwrap()
+    }
+}
+
+/// Answer the request read from `stream` with `response`, once `released`
+/// if given, and send the request to `requests`.
+fn serve<S>(stream: S, response: &str, requests: &Sender<String>, released: Option<&Receiver<()>>)
+where
+    S: Read + Write,
+{
+    let mut reader = BufReader::new(stream);
+
+    let mut request = String::new();
+    let mut content_length = 0;
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+
+        request.push_str(&line);
+
+        if line == "\r\n" {
+            break;
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    request.push_str(&String::from_utf8(body).unwrap());
+    requests.send(request).unwrap();
+
+    if let Some(released) = released {
+        released.recv().unwrap();
+    }
+
+    reader.get_mut().write_all(response.as_bytes()).unwrap();
+    reader.get_mut().flush().unwrap();
+
+    // Wait for the client to close the connection, so what it sent after
+    // the request does not reset it.
+    let _ = std::io::copy(&mut reader, &mut std::io::sink());
+}
+
+fn response(status: &str, body: &str) -> String {
+    response_with_headers(status, &[], body)
+}
+
+fn response_with_headers(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    // A JSON response, unless the headers say otherwise.
+    let content_type = match headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        true => "",
+        false => "Content-Type: application/json\r\n",
+    };
+
+    let headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        headers,
+        body
+    )
+}
+
+fn client_for(server: &MockServer) -> Client {
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url());
+    builder.build().unwrap()
+}
+
+fn validating_client_for(server: &MockServer) -> Client {
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).pipeline(SendPipeline::ValidateThenSend);
+    builder.build().unwrap()
+}
+
+#[tokio::test]
+async fn should_send_to_the_configured_base_url() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 42}"#)]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&format!("{}/", server.url()));
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = client.send(message).await.unwrap();
+
+    assert_eq!(Some(42), response.message_id);
+
+    let request = server.next_request();
+
+    assert!(request.starts_with("POST /v1/projects/project/messages:send HTTP/1.1\r\n"));
+    assert!(request.contains("authorization: Bearer api_key\r\n"));
+    assert!(request.ends_with(r#"{"validate_only":false,"message":{"topic":"token"}}"#));
+}
+
+#[tokio::test]
+async fn should_resolve_overridden_hosts() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 42}"#)]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&format!("http://fcm.test:{}", server.addr.port()));
+    builder.resolve("fcm.test", server.addr);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = client.send(message).await.unwrap();
+
+    assert_eq!(Some(42), response.message_id);
+
+    let request = server.next_request();
+
+    assert!(request.contains(&format!("host: fcm.test:{}\r\n", server.addr.port())));
+}
+
+#[tokio::test]
+#[cfg(any(feature = "native-tls", feature = "rustls", feature = "vendored-tls"))]
+async fn should_resolve_overridden_hosts_over_tls() {
+    for tls_sni in [true, false] {
+        let server = MockServer::start_tls(vec![response("200 OK", r#"{"message_id": 42}"#)]);
+
+        let mut builder = ClientBuilder::new();
+        builder
+            .base_url(&format!("https://fcm.test:{}", server.addr.port()))
+            .resolve("fcm.test", server.addr)
+            .tls_sni(tls_sni);
+        builder
+            .root_certificates
+            .push(reqwest::Certificate::from_pem(include_bytes!("testdata/ca.pem")).unwrap());
+        let client = builder.build().unwrap();
+
+        let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+        let response = client.send(message).await.unwrap();
+
+        assert_eq!(Some(42), response.message_id);
+        assert_eq!(
+            tls_sni.then(|| "fcm.test".to_string()),
+            server.next_server_name(),
+            "tls_sni({})",
+            tls_sni
+        );
+        assert!(server
+            .next_request()
+            .contains(&format!("host: fcm.test:{}\r\n", server.addr.port())));
+    }
+}
+
+#[test]
+fn should_build_the_send_url() {
+    let client = Client::new();
+
+    assert_eq!(
+        Ok("https://fcm.googleapis.com/v1/projects/my-project-1/messages:send".to_string()),
+        client.send_url("my-project-1"),
+    );
+
+    assert_eq!(
+        Ok("https://fcm.googleapis.com/v1/projects/example.com:my-project/messages:send".to_string()),
+        client.send_url("example.com:my-project"),
+    );
+}
+
+#[test]
+fn should_reject_invalid_project_ids() {
+    let client = Client::new();
+
+    for project in [
+        "",
+        "short",
+        "my-project/../../other",
+        "my-project?key=value",
+        "my-project#fragment",
+        "my-project/messages:send",
+        "My-Project",
+        "my project",
+        "1-my-project",
+        "my-project-",
+        "my-project-with-a-way-too-long-id",
+        "example.com/x:my-project",
+        ":my-project",
+        "example.com::my-project",
+    ] {
+        assert_eq!(
+            Err(FcmError::InvalidProjectId(project.to_string())),
+            client.send_url(project),
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_not_send_to_invalid_project_ids() {
+    let message = FCMRequestBuilder::to_topic("api_key", "project/../other", "token", None).finalize();
+
+    assert_eq!(
+        Err(FcmError::InvalidProjectId("project/../other".to_string())),
+        Client::new().send(message).await.map(|_| ()),
+    );
+}
+
+#[tokio::test]
+async fn should_expose_the_apns_unique_id() {
+    let server = MockServer::start(vec![
+        response_with_headers(
+            "200 OK",
+            &[("apns-unique-id", "fd3a2c0b-6b4b-4b6a-9d9c-0c98e3b5b2f8")],
+            r#"{"message_id": 42}"#,
+        ),
+        response("200 OK", r#"{"message_id": 43}"#),
+    ]);
+
+    let client = client_for(&server);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = client.send(message).await.unwrap();
+
+    assert_eq!(
+        Some("fd3a2c0b-6b4b-4b6a-9d9c-0c98e3b5b2f8".to_string()),
+        response.apns_unique_id
+    );
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = client.send(message).await.unwrap();
+
+    assert_eq!(None, response.apns_unique_id);
+}
+
+#[tokio::test]
+async fn should_send_a_validated_request_without_serializing_it_again() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 0}"#),
+        response("200 OK", r#"{"message_id": 42}"#),
+    ]);
+
+    let client = client_for(&server);
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.priority(Priority::High).data(&json!({"foo": "bar"})).unwrap();
+
+    let serializations = MESSAGE_SERIALIZATIONS.with(Cell::get);
+
+    let validated = client.validate(builder.finalize()).await.unwrap();
+    assert_eq!(Some(0), validated.response().message_id);
+
+    let response = client.send_validated(&validated).await.unwrap();
+    assert_eq!(Some(42), response.message_id);
+
+    assert_eq!(serializations + 1, MESSAGE_SERIALIZATIONS.with(Cell::get));
+
+    let message = r#"{"android":{"priority":"HIGH"},"data":{"foo":"bar"},"topic":"token"}"#;
+
+    let validation = server.next_request();
+    assert!(validation.ends_with(&format!(r#"{{"validate_only":true,"message":{}}}"#, message)));
+
+    let send = server.next_request();
+    assert!(send.ends_with(&format!(r#"{{"validate_only":false,"message":{}}}"#, message)));
+}
+
+#[tokio::test]
+async fn should_not_return_a_validated_request_when_validation_fails() {
+    let server = MockServer::start(vec![response("400 Bad Request", "{}")]);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+
+    assert_eq!(
+        Some(FcmError::InvalidMessage("Bad Request".to_string())),
+        client_for(&server).validate(message).await.err(),
+    );
+}
+
+#[test]
+fn should_reject_invalid_base_urls() {
+    for (base_url, reason) in [
+        ("fcm.googleapis.com", "relative URL without a base"),
+        ("ftp://fcm.googleapis.com", "the scheme must be http or https"),
+        (
+            "https://fcm.googleapis.com?key=value",
+            "the URL must not have a query nor a fragment",
+        ),
+        (
+            "https://fcm.googleapis.com#fragment",
+            "the URL must not have a query nor a fragment",
+        ),
+    ] {
+        let mut builder = ClientBuilder::new();
+        builder.base_url(base_url);
+
+        assert_eq!(
+            Some(ConfigError::InvalidBaseUrl {
+                base_url: base_url.to_string(),
+                reason: reason.to_string(),
+            }),
+            builder.build().err(),
+        );
+    }
+}
+
+#[test]
+fn should_reject_invalid_resolve_hosts() {
+    for host in ["", "fcm.test/path"] {
+        let mut builder = ClientBuilder::new();
+        builder.resolve(host, "127.0.0.1:443".parse().unwrap());
+
+        assert_eq!(
+            Some(ConfigError::InvalidResolveHost(host.to_string())),
+            builder.build().err(),
+        );
+    }
+}
+
+#[test]
+fn should_reject_a_retry_policy_with_a_base_delay_longer_than_its_max_delay() {
+    let mut builder = ClientBuilder::new();
+    builder.retry_policy(RetryPolicy::new(
+        3,
+        Duration::from_secs(10),
+        Duration::from_secs(5),
+        Jitter::None,
+    ));
+
+    let error = builder.build().err().unwrap();
+
+    assert_eq!(
+        ConfigError::InvalidRetryPolicy {
+            field: "retry_policy.base_delay",
+            reason: "10s is longer than the max_delay of 5s".to_string(),
+        },
+        error
+    );
+    assert_eq!(
+        "invalid retry_policy.base_delay: 10s is longer than the max_delay of 5s",
+        error.to_string()
+    );
+
+    let mut builder = ClientBuilder::new();
+    builder.retry_policy(RetryPolicy::new(
+        3,
+        Duration::from_secs(5),
+        Duration::from_secs(5),
+        Jitter::None,
+    ));
+
+    assert!(builder.build().is_ok());
+}
+
+#[tokio::test]
+async fn should_send_directly_by_default() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 42}"#)]);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", Some(true)).finalize();
+    let response = client_for(&server).send(message).await.unwrap();
+
+    assert_eq!(Some(42), response.message_id);
+    assert!(server
+        .next_request()
+        .ends_with(r#"{"validate_only":true,"message":{"topic":"token"}}"#));
+}
+
+#[tokio::test]
+async fn should_validate_before_sending() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 0}"#),
+        response("200 OK", r#"{"message_id": 42}"#),
+    ]);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = validating_client_for(&server).send(message).await.unwrap();
+
+    assert_eq!(Some(42), response.message_id);
+
+    assert!(server
+        .next_request()
+        .ends_with(r#"{"validate_only":true,"message":{"topic":"token"}}"#));
+    assert!(server
+        .next_request()
+        .ends_with(r#"{"validate_only":false,"message":{"topic":"token"}}"#));
+}
+
+#[tokio::test]
+async fn should_not_send_when_the_validation_fails() {
+    let server = MockServer::start(vec![response("400 Bad Request", "{}")]);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+
+    assert_eq!(
+        Some(FcmError::InvalidMessage("Bad Request".to_string())),
+        validating_client_for(&server).send(message).await.err(),
+    );
+
+    assert!(server.next_request().contains(r#""validate_only":true"#));
+}
+
+#[tokio::test]
+async fn should_return_the_send_error_after_a_successful_validation() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 0}"#),
+        response("503 Service Unavailable", "{}"),
+    ]);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+
+    assert_eq!(
+        Some(FcmError::ServerError(None)),
+        validating_client_for(&server).send(message).await.err(),
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_send_concurrently_from_clones() {
+    let server = MockServer::start((0..8).map(|_| response("200 OK", r#"{"message_id": 42}"#)).collect());
+
+    let client = client_for(&server);
+
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let client = client.clone();
+
+            tokio::spawn(async move {
+                let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+                client.send(message).await.unwrap().message_id
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        assert_eq!(Some(42), task.await.unwrap());
+    }
+
+    for _ in 0..8 {
+        assert!(server
+            .next_request()
+            .starts_with("POST /v1/projects/project/messages:send"));
+    }
+}
+
+#[tokio::test]
+async fn should_send_over_http1_only() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 42}"#)]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).http1_only(true);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+
+    assert_eq!(Some(42), client.send(message).await.unwrap().message_id);
+    assert!(server.next_request().contains(" HTTP/1.1\r\n"));
+}
+
+#[tokio::test]
+async fn should_fall_back_to_http1_once_http2_fails() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 41}"#),
+        response("200 OK", r#"{"message_id": 42}"#),
+        response("200 OK", r#"{"message_id": 43}"#),
+    ]);
+
+    // The mock server only speaks HTTP/1.1, so forcing HTTP/2 on it fails
+    // like behind a proxy breaking the negotiation.
+    let mut client = client_for(&server);
+    client.http_client = reqwest::Client::builder().http2_prior_knowledge().build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = client.send(message).await.unwrap();
+
+    assert_eq!(Some(42), response.message_id);
+    assert!(response.downgraded_to_http1);
+    assert!(server.next_request().starts_with("PRI * HTTP/2.0\r\n"));
+    assert!(server.next_request().contains(" HTTP/1.1\r\n"));
+
+    let clone = client.clone();
+    assert!(clone.is_downgraded_to_http1());
+    assert_eq!(1, clone.http1_downgrades());
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = clone.send(message).await.unwrap();
+
+    assert_eq!(Some(43), response.message_id);
+    assert!(!response.downgraded_to_http1);
+    assert!(server.next_request().contains(" HTTP/1.1\r\n"));
+    assert_eq!(1, client.http1_downgrades());
+}
+
+#[tokio::test]
+async fn should_not_fall_back_to_http1_when_only_using_it() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 42}"#)]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).http1_only(true);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let response = client.send(message).await.unwrap();
+
+    assert!(!response.downgraded_to_http1);
+    assert!(!client.is_downgraded_to_http1());
+    assert_eq!(0, client.http1_downgrades());
+}
+
+#[tokio::test]
+async fn should_add_the_headers_of_the_options_to_a_single_send() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 42}"#),
+        response("200 OK", r#"{"message_id": 43}"#),
+    ]);
+
+    let client = client_for(&server);
+
+    let mut options = SendOptions::new();
+    options.header("X-Proxy-Authorization", "secret").unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    client.send_with(message, &options).await.unwrap();
+
+    let request = server.next_request();
+    assert_eq!(1, request.matches("x-proxy-authorization: secret\r\n").count());
+    assert_eq!(1, request.matches("authorization: Bearer api_key\r\n").count());
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    client.send(message).await.unwrap();
+
+    assert!(!server.next_request().contains("x-proxy-authorization"));
+}
+
+#[test]
+fn should_not_override_the_client_headers() {
+    let mut options = SendOptions::new();
+
+    for name in ["Authorization", "content-type", "Content-Length"] {
+        assert_eq!(
+            Some(ConfigError::ReservedHeader(name.to_ascii_lowercase())),
+            options.header(name, "value").err(),
+        );
+    }
+
+    assert_eq!(
+        Some(ConfigError::InvalidHeader("x header".to_string())),
+        options.header("x header", "value").err(),
+    );
+}
+
+#[test]
+fn should_use_the_standard_retry_policy_by_default() {
+    assert_eq!(RetryPolicy::standard(), Client::new().retry_policy());
+
+    let mut builder = ClientBuilder::new();
+    builder.retry_policy(RetryPolicy::campaign());
+
+    assert_eq!(RetryPolicy::campaign(), builder.build().unwrap().retry_policy());
+}
+
+#[test]
+fn should_round_trip_send_options() {
+    let mut options = SendOptions::new();
+    options
+        .header("x-proxy-authorization", "secret")
+        .unwrap()
+        .header("x-tag", "a")
+        .unwrap()
+        .header("x-tag", "b")
+        .unwrap();
+
+    let serialized = serde_json::to_value(&options).unwrap();
+
+    assert_eq!(
+        json!({"headers": [["x-proxy-authorization", "secret"], ["x-tag", "a"], ["x-tag", "b"]]}),
+        serialized
+    );
+    assert_eq!(options, serde_json::from_value(serialized).unwrap());
+}
+
+#[test]
+fn should_deserialize_send_options_from_other_versions() {
+    let options: SendOptions = serde_json::from_value(json!({"deadline": "10s"})).unwrap();
+
+    assert_eq!(SendOptions::new(), options);
+
+    let options: SendOptions =
+        serde_json::from_value(json!({"headers": [["x-tag", "a"]], "priority": "HIGH"})).unwrap();
+
+    let mut expected = SendOptions::new();
+    expected.header("x-tag", "a").unwrap();
+
+    assert_eq!(expected, options);
+}
+
+#[test]
+fn should_not_deserialize_reserved_send_options_headers() {
+    let error = serde_json::from_value::<SendOptions>(json!({"headers": [["authorization", "Bearer other"]]}))
+        .err()
+        .unwrap();
+
+    assert!(error.to_string().contains("\"authorization\" cannot be overridden"));
+}
+
+#[test]
+fn should_merge_send_options() {
+    let mut options = SendOptions::new();
+    options
+        .header("x-tag", "a")
+        .unwrap()
+        .header("x-tag", "b")
+        .unwrap()
+        .header("x-team", "push")
+        .unwrap();
+
+    let mut overrides = SendOptions::new();
+    overrides.header("x-tag", "c").unwrap();
+
+    options.merge(&overrides);
+
+    let mut expected = SendOptions::new();
+    expected.header("x-team", "push").unwrap().header("x-tag", "c").unwrap();
+
+    assert_eq!(expected, options);
+}
+
+fn error_body(code: &str) -> String {
+    let body = json!({
+        "error": {
+            "details": [
+                {"@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError", "errorCode": code},
+            ],
+        },
+    });
+
+    body.to_string()
+}
+
+fn unregistered() -> String {
+    response("404 Not Found", &error_body("UNREGISTERED"))
+}
+
+#[tokio::test]
+async fn should_expose_the_error_code_of_rejected_messages() {
+    let server = MockServer::start(vec![unregistered()]);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    let error = client_for(&server).send(message).await.err().unwrap();
+
+    assert_eq!(
+        FcmError::Rejected {
+            code: FcmErrorCode::Unregistered,
+            retry_after: None,
+        },
+        error
+    );
+    assert_eq!(Some(&FcmErrorCode::Unregistered), error.error_code());
+}
+
+#[tokio::test]
+async fn should_not_retry_an_empty_success_body_by_default() {
+    let server = MockServer::start(vec![response("200 OK", "")]);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    let error = client_for(&server).send(message).await.err().unwrap();
+
+    assert_eq!(FcmError::EmptySuccessBody, error);
+    assert_eq!(Triage::Operations, error.triage());
+}
+
+#[tokio::test]
+async fn should_report_a_success_response_which_is_not_json() {
+    let page = "<html><body>Access denied by the egress proxy</body></html>";
+    let server = MockServer::start(vec![response_with_headers(
+        "200 OK",
+        &[("Content-Type", "text/html; charset=utf-8")],
+        page,
+    )]);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    let error = client_for(&server).send(message).await.err().unwrap();
+
+    assert_eq!(
+        FcmError::UnexpectedContentType {
+            content_type: Some("text/html; charset=utf-8".to_string()),
+            snippet: page.to_string(),
+        },
+        error
+    );
+    assert_eq!(Triage::Operations, error.triage());
+}
+
+#[tokio::test]
+async fn should_retry_an_empty_success_body_when_configured() {
+    let server = MockServer::start(vec![response_with_headers("200 OK", &[("Retry-After", "30")], "")]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).retry_empty_success_body(true);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    let error = builder.build().unwrap().send(message).await.err().unwrap();
+
+    assert_eq!(
+        FcmError::ServerError(Some(RetryAfter::Delay(chrono::Duration::seconds(30)))),
+        error
+    );
+    assert_eq!(Triage::Transient, error.triage());
+}
+
+#[tokio::test]
+async fn should_prefer_the_error_code_to_the_status() {
+    let cases = vec![
+        (
+            response("400 Bad Request", &error_body("INVALID_ARGUMENT")),
+            FcmErrorCode::InvalidArgument,
+            None,
+        ),
+        (
+            response("503 Service Unavailable", &error_body("UNAVAILABLE")),
+            FcmErrorCode::Unavailable,
+            None,
+        ),
+        (
+            response("500 Internal Server Error", &error_body("INTERNAL")),
+            FcmErrorCode::Internal,
+            None,
+        ),
+        (
+            response_with_headers(
+                "429 Too Many Requests",
+                &[("Retry-After", "30")],
+                &error_body("QUOTA_EXCEEDED"),
+            ),
+            FcmErrorCode::QuotaExceeded,
+            Some(RetryAfter::Delay(chrono::Duration::seconds(30))),
+        ),
+    ];
+
+    for (response, code, retry_after) in cases {
+        let server = MockServer::start(vec![response]);
+
+        let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+        let error = client_for(&server).send(message).await.err().unwrap();
+
+        assert_eq!(code.triage(), error.triage());
+        assert_eq!(retry_after.as_ref(), error.retry_after());
+        assert_eq!(FcmError::Rejected { code, retry_after }, error);
+    }
+}
+
+#[tokio::test]
+async fn should_fall_back_to_the_status_without_error_code() {
+    let cases = vec![
+        (response("401 Unauthorized", "{}"), FcmError::Unauthorized),
+        (
+            response("400 Bad Request", "{}"),
+            FcmError::InvalidMessage("Bad Request".to_string()),
+        ),
+        (
+            response_with_headers("503 Service Unavailable", &[("Retry-After", "30")], "{}"),
+            FcmError::ServerError(Some(RetryAfter::Delay(chrono::Duration::seconds(30)))),
+        ),
+        (
+            response("404 Not Found", "{}"),
+            FcmError::InvalidMessage("Unknown Error".to_string()),
+        ),
+    ];
+
+    for (response, expected) in cases {
+        let server = MockServer::start(vec![response]);
+
+        let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+
+        assert_eq!(Some(expected), client_for(&server).send(message).await.err());
+    }
+}
+
+#[tokio::test]
+async fn should_fan_out_to_each_token() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 1}"#),
+        unregistered(),
+        response("503 Service Unavailable", "{}"),
+        response("200 OK", r#"{"message_id": 4}"#),
+    ]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).multicast_concurrency(1);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let tokens = ["one", "two", "three", "four"];
+    let response = client.send_multi(message, &tokens).await;
+
+    assert_eq!(2, response.success_count());
+    assert_eq!(2, response.failure_count());
+    assert_eq!(vec!["two"], response.unregistered_tokens());
+
+    let tokens_and_ids: Vec<_> = response
+        .results
+        .iter()
+        .map(|result| {
+            (
+                result.token.as_str(),
+                result.result.as_ref().ok().and_then(|r| r.message_id),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        vec![("one", Some(1)), ("two", None), ("three", None), ("four", Some(4))],
+        tokens_and_ids
+    );
+
+    for token in tokens {
+        assert!(server.next_request().ends_with(&format!(
+            r#"{{"validate_only":false,"message":{{"token":"{}"}}}}"#,
+            token
+        )));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_fan_out_concurrently_in_order() {
+    let server = MockServer::start((0..20).map(|_| response("200 OK", r#"{"message_id": 42}"#)).collect());
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).multicast_concurrency(4);
+    let client = builder.build().unwrap();
+
+    let tokens: Vec<_> = (0..20).map(|i| format!("token-{}", i)).collect();
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let response = client.send_multi(message, &tokens).await;
+
+    assert_eq!(20, response.success_count());
+
+    let sent: Vec<_> = response.results.iter().map(|result| result.token.clone()).collect();
+    assert_eq!(tokens, sent);
+}
+
+#[tokio::test]
+async fn should_send_nothing_to_no_tokens() {
+    let server = MockServer::start(vec![]);
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let tokens: [&str; 0] = [];
+    let response = client_for(&server).send_multi(message, &tokens).await;
+
+    assert!(response.results.is_empty());
+    assert_eq!(0, response.failure_count());
+}
+
+#[tokio::test]
+async fn should_send_to_tokens_in_batches() {
+    let server = MockServer::start(
+        (0..5)
+            .map(|i| response("200 OK", &format!(r#"{{"message_id": {}}}"#, i)))
+            .collect(),
+    );
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .base_url(&server.url())
+        .multicast_concurrency(2)
+        .multicast_batch_size(2);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let response = client.send_multi(message, &["a", "b", "c", "d", "e"]).await;
+
+    assert_eq!(5, response.success_count());
+
+    let tokens: Vec<_> = response.results.iter().map(|result| result.token.as_str()).collect();
+    assert_eq!(vec!["a", "b", "c", "d", "e"], tokens);
+}
+
+#[tokio::test]
+async fn should_fail_the_remaining_batches_after_a_failed_batch() {
+    let server = MockServer::start(vec![
+        response("401 Unauthorized", "{}"),
+        response("401 Unauthorized", "{}"),
+    ]);
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .base_url(&server.url())
+        .multicast_concurrency(1)
+        .multicast_batch_size(2);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let response = client.send_multi(message, &["a", "b", "c", "d", "e"]).await;
+
+    assert_eq!(5, response.failure_count());
+
+    for result in &response.results {
+        assert_eq!(Some(&FcmError::Unauthorized), result.result.as_ref().err());
+    }
+}
+
+#[tokio::test]
+async fn should_keep_sending_after_a_partially_failed_batch() {
+    let server = MockServer::start(vec![
+        response("401 Unauthorized", "{}"),
+        response("200 OK", r#"{"message_id": 2}"#),
+        response("200 OK", r#"{"message_id": 3}"#),
+    ]);
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .base_url(&server.url())
+        .multicast_concurrency(1)
+        .multicast_batch_size(2);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let response = client.send_multi(message, &["a", "b", "c"]).await;
+
+    assert_eq!(2, response.success_count());
+    assert_eq!(1, response.failure_count());
+}
+
+#[tokio::test]
+async fn should_send_once_to_duplicate_tokens() {
+    let server = MockServer::start(vec![
+        response("200 OK", r#"{"message_id": 1}"#),
+        unregistered(),
+        response("200 OK", r#"{"message_id": 3}"#),
+    ]);
+
+    let mut builder = ClientBuilder::new();
+    builder
+        .base_url(&server.url())
+        .multicast_concurrency(1)
+        .dedupe_tokens(true);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let response = client.send_multi(message, &["a", "b", "a", "c", "b", "a"]).await;
+
+    let tokens_and_ids: Vec<_> = response
+        .results
+        .iter()
+        .map(|result| {
+            (
+                result.token.as_str(),
+                result.result.as_ref().ok().and_then(|r| r.message_id),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        vec![
+            ("a", Some(1)),
+            ("b", None),
+            ("a", Some(1)),
+            ("c", Some(3)),
+            ("b", None),
+            ("a", Some(1)),
+        ],
+        tokens_and_ids
+    );
+    assert_eq!(vec!["b", "b"], response.unregistered_tokens());
+
+    for token in ["a", "b", "c"] {
+        assert!(server
+            .next_request()
+            .ends_with(&format!(r#""message":{{"token":"{}"}}}}"#, token)));
+    }
+}
+
+#[tokio::test]
+async fn should_send_nothing_to_no_tokens_when_deduplicating() {
+    let server = MockServer::start(vec![]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).dedupe_tokens(true);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let tokens: Vec<String> = Vec::new();
+    let response = client.send_multi(message, &tokens).await;
+
+    assert!(response.results.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_wait_for_the_inflight_bytes_budget() {
+    let (server, release) = MockServer::start_held(vec![
+        response("200 OK", r#"{"message_id": 1}"#),
+        response("200 OK", r#"{"message_id": 2}"#),
+    ]);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    let size = message.payload().unwrap().len();
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).max_inflight_bytes(size + size / 2);
+    let client = builder.build().unwrap();
+
+    let first = tokio::spawn({
+        let client = client.clone();
+        let message = message.clone();
+        async move { client.send(message).await }
+    });
+
+    server.next_request();
+    assert_eq!(size, client.inflight_bytes());
+
+    let second = tokio::spawn({
+        let client = client.clone();
+        let message = message.clone();
+        async move { client.send(message).await }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(!second.is_finished());
+    assert_eq!(size, client.inflight_bytes());
+
+    release.send(()).unwrap();
+    assert_eq!(Some(1), first.await.unwrap().unwrap().message_id);
+
+    server.next_request();
+    release.send(()).unwrap();
+    assert_eq!(Some(2), second.await.unwrap().unwrap().message_id);
+
+    assert_eq!(0, client.inflight_bytes());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_not_share_the_inflight_bytes_budget_with_a_detached_client() {
+    let (server, release) = MockServer::start_held(vec![
+        response("200 OK", r#"{"message_id": 1}"#),
+        response("200 OK", r#"{"message_id": 2}"#),
+    ]);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    let size = message.payload().unwrap().len();
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).max_inflight_bytes(size + size / 2);
+    let client = builder.build().unwrap();
+    let detached = client.detached();
+
+    let first = tokio::spawn({
+        let client = client.clone();
+        let message = message.clone();
+        async move { client.send(message).await }
+    });
+
+    server.next_request();
+    assert_eq!(size, client.inflight_bytes());
+    assert_eq!(0, detached.inflight_bytes());
+
+    let second = tokio::spawn({
+        let detached = detached.clone();
+        let message = message.clone();
+        async move { detached.send(message).await }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(size, client.inflight_bytes());
+    assert_eq!(size, detached.inflight_bytes());
+
+    release.send(()).unwrap();
+    assert_eq!(Some(1), first.await.unwrap().unwrap().message_id);
+    assert_eq!(0, client.inflight_bytes());
+
+    server.next_request();
+    release.send(()).unwrap();
+    assert_eq!(Some(2), second.await.unwrap().unwrap().message_id);
+
+    assert_eq!(0, detached.inflight_bytes());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_send_a_body_larger_than_the_budget_alone() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 1}"#)]);
+
+    let mut builder = ClientBuilder::new();
+    builder.base_url(&server.url()).max_inflight_bytes(1);
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+
+    assert_eq!(Some(1), client.send(message).await.unwrap().message_id);
+    assert_eq!(0, client.inflight_bytes());
+}
+
+#[tokio::test]
+async fn should_send_the_charset_of_the_body() {
+    let server = MockServer::start(vec![response("200 OK", r#"{"message_id": 1}"#)]);
+
+    let message = FCMRequestBuilder::to_token("api_key", "project", "token", None).finalize();
+    client_for(&server).send(message).await.unwrap();
+
+    assert!(server
+        .next_request()
+        .to_ascii_lowercase()
+        .contains("\r\ncontent-type: application/json; charset=utf-8\r\n"));
+}
+
+/// The length of the body of a request received by a `MockServer`.
+fn body_length(request: &str) -> usize {
+    request.split_once("\r\n\r\n").unwrap().1.len()
+}
+
+#[tokio::test]
+async fn should_estimate_what_send_multi_sends() {
+    let tokens = ["a", "bb", "a", "ccc", "bb", "dddd", "eeeee"];
+
+    for pipeline in [SendPipeline::Direct, SendPipeline::ValidateThenSend] {
+        let server = MockServer::start((0..10).map(|_| response("200 OK", r#"{"message_id": 42}"#)).collect());
+
+        let mut builder = ClientBuilder::new();
+        builder
+            .base_url(&server.url())
+            .pipeline(pipeline)
+            .multicast_batch_size(2)
+            .dedupe_tokens(true);
+        let client = builder.build().unwrap();
+
+        let mut message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None);
+        message.collapse_key("scores");
+        let message = message.finalize();
+
+        let estimate = client.estimate(&message, &tokens, 0.0);
+        let response = client.send_multi(message, &tokens).await;
+
+        let requests = match pipeline {
+            SendPipeline::Direct => 5,
+            SendPipeline::ValidateThenSend => 10,
+        };
+        let payload_bytes: usize = (0..requests).map(|_| body_length(&server.next_request())).sum();
+
+        assert_eq!(tokens.len(), response.success_count());
+        assert_eq!(5, estimate.tokens);
+        assert_eq!(requests, estimate.requests);
+        assert_eq!(3, estimate.batches);
+        assert_eq!(payload_bytes, estimate.payload_bytes);
+        assert_eq!(0.0, estimate.expected_retries);
+    }
+}
+
+#[test]
+fn should_estimate_the_retries_from_the_failure_rate() {
+    let mut builder = ClientBuilder::new();
+    builder.retry_policy(RetryPolicy::aggressive());
+    let client = builder.build().unwrap();
+
+    let message = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let tokens: Vec<_> = (0..100).map(|i| i.to_string()).collect();
+
+    let estimate = client.estimate(&message, &tokens, 0.5);
+
+    assert_eq!(1, estimate.batches);
+    assert!((estimate.expected_retries - 75.0).abs() < 1e-9);
+}
@@ -12,12 +12,12 @@
 //! # use std::collections::HashMap;
 //! # #[tokio::main]
 //! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-//! let client = fcm::Client::new();
+//! let client = fcm_http1::Client::new();
 //!
 //! let mut map = HashMap::new();
 //! map.insert("message", "Howdy!");
 //!
-//! let mut builder = fcm::FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", None);
+//! let mut builder = fcm_http1::FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
 //! builder.data(&map);
 //!
 //! let response = client.send(builder.finalize()).await?;
@@ -30,7 +30,7 @@
 //!
 //! ```rust
 //! # fn main() {
-//! let mut builder = fcm::NotificationBuilder::new();
+//! let mut builder = fcm_http1::NotificationBuilder::new();
 //! builder.title("Hey!");
 //! builder.body("Do you want to catch up later?");
 //! let notification = builder.finalize();
@@ -42,14 +42,14 @@
 //! ```no_run
 //! # #[tokio::main]
 //! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-//! let client = fcm::Client::new();
+//! let client = fcm_http1::Client::new();
 //!
-//! let mut notification_builder = fcm::NotificationBuilder::new();
+//! let mut notification_builder = fcm_http1::NotificationBuilder::new();
 //! notification_builder.title("Hey!");
 //! notification_builder.body("Do you want to catch up later?");
 //!
 //! let notification = notification_builder.finalize();
-//! let mut message_builder = fcm::FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", None);
+//! let mut message_builder = fcm_http1::FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
 //! message_builder.notification(notification);
 //!
 //! let response = client.send(message_builder.finalize()).await?;
@@ -62,7 +62,58 @@ mod message;
 pub use crate::message::*;
 mod notification;
 pub use crate::notification::*;
+mod android;
+pub use crate::android::*;
+mod apns;
+pub use crate::apns::*;
 mod client;
 pub use crate::client::*;
+pub mod campaign;
+pub mod preview;
+pub mod wire_time;
 
 pub use crate::client::response::FcmError as Error;
+
+/// Re-exports of everything needed to build and send messages.
+///
+/// # Examples:
+///
+/// ```no_run
+/// use fcm_http1::prelude::*;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), FcmError> {
+/// let client = Client::new();
+///
+/// let mut notification_builder = NotificationBuilder::new();
+/// notification_builder.title("Hey!");
+///
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+/// builder.priority(Priority::High);
+/// builder.notification(notification_builder.finalize());
+///
+/// let response: FcmResponse = client.send(builder.finalize()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub mod prelude {
+    pub use crate::android::{
+        AndroidConfig, AndroidConfigBuilder, AndroidNotification, AndroidNotificationBuilder, Color, LightSettings,
+        NotificationPriority, Sound, Visibility,
+    };
+    pub use crate::apns::{ApnsConfig, ApnsConfigBuilder};
+    pub use crate::client::response::{
+        ErrorReason, FcmError, FcmErrorCode, FcmResponse, MessageResult, MulticastResponse, MulticastResult,
+        RetryAfter, Triage,
+    };
+    pub use crate::client::{
+        Client, ClientBuilder, ConfigError, Jitter, RetryPolicy, SendOptions, SendPipeline, ValidatedRequest,
+    };
+    #[allow(deprecated)]
+    pub use crate::message::MessageBuildError;
+    pub use crate::message::{
+        BuildError, DataMapBuilder, EmptyStrings, FCMRequest, FCMRequestBuilder, FcmOptions, FcmOptionsBuilder,
+        Priority,
+    };
+    pub use crate::notification::{Notification, NotificationBuilder};
+}
@@ -1,102 +1,330 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::{error::Error, fmt};
 
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
 
+use crate::android::{is_valid_color, AndroidConfig, AndroidConfigBuilder};
+use crate::apns::ApnsConfig;
 use crate::notification::Notification;
 
+pub mod well_known;
+
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
 
-#[derive(Serialize, PartialEq, Debug)]
-#[serde(rename_all = "lowercase")]
+#[derive(Serialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum Priority {
     Normal,
     High,
 }
 
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct MessageBody<'a> {
     validate_only: bool,
     message: Message<'a>,
 }
 
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct Message<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    collapse_key: Option<&'a str>,
+    android: Option<AndroidConfig<'a>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    content_available: Option<bool>,
+    apns: Option<ApnsConfig<'a>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    data: Option<Value>,
+    condition: Option<&'a str>,
 
+    /// A `BTreeMap`, so the keys serialize sorted whatever order they were
+    /// set in, even when `serde_json` preserves the order of its own maps.
     #[serde(skip_serializing_if = "Option::is_none")]
-    delay_while_idle: Option<bool>,
+    data: Option<BTreeMap<String, Value>>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    notification: Option<Notification<'a>>,
+    delay_while_idle: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    priority: Option<Priority>,
+    fcm_options: Option<FcmOptions<'a>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    registration_ids: Option<Vec<Cow<'a, str>>>,
+    notification: Option<Notification<'a>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    restricted_package_name: Option<&'a str>,
+    registration_ids: Option<Vec<Cow<'a, str>>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    time_to_live: Option<i32>,
+    token: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     topic: Option<&'a str>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    mutable_content: Option<bool>,
 }
 
 /// Represents a FCM message. Construct the FCM message
 /// using various utility methods and finally send it.
 /// # Examples:
 /// ```rust
-/// use fcm::FCMRequestBuilder;
+/// use fcm_http1::FCMRequestBuilder;
 ///
-/// let mut builder = FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", Some(true));
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", Some(true));
 /// let message = builder.finalize();
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FCMRequest<'a> {
     pub api_key: &'a str,
     pub project: &'a str,
     pub body: MessageBody<'a>,
+    prune_empty: bool,
+    preserve_empty: Vec<&'a str>,
+    clear_badge: bool,
+    empty_strings: EmptyStrings,
+    empty_strings_for: Vec<(&'a str, EmptyStrings)>,
 }
 
+impl<'a> FCMRequest<'a> {
+    /// Serialize the body sent to FCM.
+    pub(crate) fn payload(&self) -> Result<Vec<u8>, serde_json::Error> {
+        Ok(envelope(self.body.validate_only, &self.message_payload()?))
+    }
+
+    /// Serialize the message alone, without the `validate_only` envelope.
+    pub(crate) fn message_payload(&self) -> Result<Vec<u8>, serde_json::Error> {
+        #[cfg(test)]
+        tests::MESSAGE_SERIALIZATIONS.with(|count| count.set(count.get() + 1));
+
+        if !self.prune_empty {
+            return serde_json::to_vec(&self.body.message);
+        }
+
+        let mut message = serde_json::to_value(&self.body.message)?;
+
+        if let Value::Object(ref mut message) = message {
+            prune_empty(message, "", &self.preserve_empty);
+        }
+
+        serde_json::to_vec(&message)
+    }
+
+    /// A SHA-256 fingerprint of the message, stable across runs and
+    /// independent of the order its fields and data keys were set in.
+    ///
+    /// It is computed over the message as sent (after `prune_empty`), whose
+    /// serialization is already stable: the fields are in a fixed order and
+    /// the data keys are sorted. The `validate_only` parameter, the API key
+    /// and the project are not part of it, so validating and then sending a
+    /// message gives the same fingerprint. Fails with a
+    /// `BuildError::Serialization` when the message cannot be serialized.
+    pub fn fingerprint(&self) -> Result<[u8; 32], BuildError> {
+        Ok(Sha256::digest(self.message_payload()?).into())
+    }
+
+    /// The `fingerprint` of the message, as a lowercase hex string.
+    pub fn fingerprint_hex(&self) -> Result<String, BuildError> {
+        Ok(self.fingerprint()?.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// Convert the request back into a `FCMRequestBuilder`, so it can be
+    /// modified and finalized again. The builder keeps clearing the badge,
+    /// and the policies of `FCMRequestBuilder::empty_strings`, which
+    /// `try_finalize` applies again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fcm_http1::{FCMRequestBuilder, Priority};
+    ///
+    /// let message = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None).finalize();
+    ///
+    /// let mut builder = message.to_builder();
+    /// builder.priority(Priority::High);
+    /// let message = builder.finalize();
+    /// ```
+    pub fn to_builder(&self) -> FCMRequestBuilder<'a> {
+        let message = &self.body.message;
+
+        FCMRequestBuilder {
+            api_key: self.api_key,
+            project: self.project,
+            validate_only: Some(self.body.validate_only),
+            prune_empty: self.prune_empty,
+            preserve_empty: self.preserve_empty.clone(),
+            empty_strings: self.empty_strings,
+            empty_strings_for: self.empty_strings_for.clone(),
+            message: MessageFields {
+                condition: message.condition,
+                token: message.token.clone(),
+                topic: message.topic,
+                registration_ids: message.registration_ids.clone(),
+                apns: message.apns.clone(),
+                clear_badge: self.clear_badge,
+                content_available: None,
+                #[cfg(feature = "legacy-http")]
+                delay_while_idle: message.delay_while_idle,
+                fcm_options: message.fcm_options,
+                android: message.android.clone(),
+                data: message.data.clone(),
+                notification: message.notification.clone(),
+                mutable_content: None,
+            },
+        }
+    }
+}
+
+/// Wrap an already serialized message into the body sent to FCM, the same
+/// way `MessageBody` serializes.
+pub(crate) fn envelope(validate_only: bool, message: &[u8]) -> Vec<u8> {
+    let head: &[u8] = if validate_only {
+        br#"{"validate_only":true,"message":"#
+    } else {
+        br#"{"validate_only":false,"message":"#
+    };
+
+    let mut body = Vec::with_capacity(head.len() + message.len() + 1);
+    body.extend_from_slice(head);
+    body.extend_from_slice(message);
+    body.push(b'}');
+    body
+}
+
+/// Remove the nulls, empty objects and empty arrays from `object`, except for
+/// the `preserved` paths. Paths are relative to the message, with the keys
+/// separated by dots, e.g. `notification`.
+fn prune_empty(object: &mut Map<String, Value>, path: &str, preserved: &[&str]) {
+    object.retain(|key, value| {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+
+        if let Value::Object(ref mut child) = value {
+            prune_empty(child, &path, preserved);
+        }
+
+        let empty = match value {
+            Value::Null => true,
+            Value::Object(child) => child.is_empty(),
+            Value::Array(items) => items.is_empty(),
+            _ => false,
+        };
+
+        !empty || preserved.contains(&path.as_str())
+    });
+}
+
+/// A value usable as a registration id: `&str`, `String`, `Cow<str>` or a
+/// reference to one of them, so both `&["id"]` and `Vec<String>` can be
+/// passed to `FCMRequestBuilder::registration_ids`.
+pub trait IntoRegistrationId<'a> {
+    /// Convert into a registration id, without copying the string.
+    fn into_registration_id(self) -> Cow<'a, str>;
+}
+
+impl<'a> IntoRegistrationId<'a> for &'a str {
+    fn into_registration_id(self) -> Cow<'a, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<'a> IntoRegistrationId<'a> for &&'a str {
+    fn into_registration_id(self) -> Cow<'a, str> {
+        Cow::Borrowed(*self)
+    }
+}
+
+impl<'a> IntoRegistrationId<'a> for String {
+    fn into_registration_id(self) -> Cow<'a, str> {
+        Cow::Owned(self)
+    }
+}
+
+impl<'a> IntoRegistrationId<'a> for &'a String {
+    fn into_registration_id(self) -> Cow<'a, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<'a> IntoRegistrationId<'a> for Cow<'a, str> {
+    fn into_registration_id(self) -> Cow<'a, str> {
+        self
+    }
+}
+
+/// The fields of the message being built by a `FCMRequestBuilder`.
 #[derive(Debug)]
-pub struct MessageBuilder<'a> {
-    collapse_key: Option<&'a str>,
+struct MessageFields<'a> {
+    android: Option<AndroidConfig<'a>>,
+    apns: Option<ApnsConfig<'a>>,
+    clear_badge: bool,
+    condition: Option<&'a str>,
     content_available: Option<bool>,
-    data: Option<Value>,
+    data: Option<BTreeMap<String, Value>>,
+    #[cfg(feature = "legacy-http")]
     delay_while_idle: Option<bool>,
+    fcm_options: Option<FcmOptions<'a>>,
     notification: Option<Notification<'a>>,
-    priority: Option<Priority>,
     registration_ids: Option<Vec<Cow<'a, str>>>,
-    restricted_package_name: Option<&'a str>,
-    time_to_live: Option<i32>,
+    token: Option<Cow<'a, str>>,
     topic: Option<&'a str>,
     mutable_content: Option<bool>,
 }
 
+impl<'a> MessageFields<'a> {
+    /// The string fields `FCMRequestBuilder::empty_strings` applies to, by
+    /// path in the message.
+    fn string_fields(&mut self) -> Vec<(&'static str, &mut Option<&'a str>)> {
+        let mut fields = vec![("condition", &mut self.condition), ("topic", &mut self.topic)];
+
+        if let Some(ref mut fcm_options) = self.fcm_options {
+            fields.push(("fcm_options.analytics_label", &mut fcm_options.analytics_label));
+        }
+
+        if let Some(ref mut notification) = self.notification {
+            fields.extend(notification.string_fields());
+        }
+
+        if let Some(ref mut android) = self.android {
+            fields.extend(android.string_fields());
+        }
+
+        if let Some(ref mut apns) = self.apns {
+            fields.extend(apns.string_fields());
+        }
+
+        fields
+    }
+}
+
+/// What `FCMRequestBuilder::try_finalize` does with the string fields set to
+/// an empty string, which FCM does not handle like unset ones, e.g. for
+/// `android.collapse_key`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum EmptyStrings {
+    /// Fail with a `BuildError::Validation` of the field. The default.
+    #[default]
+    Reject,
+
+    /// Unset the field, as if it was never set.
+    TreatAsUnset,
+
+    /// Send the empty string as is.
+    Allow,
+}
+
 ///
 /// A builder to get a `FCMRequest` instance. If the validate_only parameter is not defined, it will default to false (i.e. the message will actually be sent)
 ///
 /// # Examples
 ///
 /// ```rust
-/// use fcm::FCMRequestBuilder;
+/// use fcm_http1::FCMRequestBuilder;
 ///
-/// let mut builder = FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", None);
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
 /// let message = builder.finalize();
 /// ```
 #[derive(Debug)]
@@ -104,25 +332,40 @@ pub struct FCMRequestBuilder<'a> {
     api_key: &'a str,
     project: &'a str,
     validate_only: Option<bool>,
-    message: MessageBuilder<'a>,
+    prune_empty: bool,
+    preserve_empty: Vec<&'a str>,
+    empty_strings: EmptyStrings,
+    empty_strings_for: Vec<(&'a str, EmptyStrings)>,
+    message: MessageFields<'a>,
 }
 
+/// The former name of `FCMRequestBuilder`.
+#[deprecated(note = "use `FCMRequestBuilder` instead")]
+pub type MessageBuilder<'a> = FCMRequestBuilder<'a>;
+
 impl<'a> FCMRequestBuilder<'a> {
-    /// Get a new instance of FCMRequest. You need to supply topic.
-    pub fn new(api_key: &'a str, project: &'a str, topic: &'a str, validate_only: Option<bool>) -> Self {
+    /// A builder with no target yet, which every constructor starts from.
+    fn untargeted(api_key: &'a str, project: &'a str, validate_only: Option<bool>) -> Self {
         FCMRequestBuilder {
             api_key,
             project,
             validate_only,
-            message: MessageBuilder {
-                topic: Some(topic),
+            prune_empty: false,
+            preserve_empty: Vec::new(),
+            empty_strings: EmptyStrings::default(),
+            empty_strings_for: Vec::new(),
+            message: MessageFields {
+                condition: None,
+                token: None,
+                topic: None,
                 registration_ids: None,
-                collapse_key: None,
-                priority: None,
+                apns: None,
+                clear_badge: false,
                 content_available: None,
+                #[cfg(feature = "legacy-http")]
                 delay_while_idle: None,
-                time_to_live: None,
-                restricted_package_name: None,
+                fcm_options: None,
+                android: None,
                 data: None,
                 notification: None,
                 mutable_content: None,
@@ -130,121 +373,287 @@ impl<'a> FCMRequestBuilder<'a> {
         }
     }
 
-    /// Get a new instance of FCMRequest. You need to supply registration ids.
-    pub fn new_multi<S>(api_key: &'a str, project: &'a str, ids: &'a [S], validate_only: Option<bool>) -> Self
+    /// Get a new instance of FCMRequest, sent to the single device of the
+    /// registration `token`.
+    pub fn to_token<T>(api_key: &'a str, project: &'a str, token: T, validate_only: Option<bool>) -> Self
     where
-        S: Into<Cow<'a, str>> + AsRef<str>,
+        T: Into<Cow<'a, str>>,
     {
-        let converted = ids.iter().map(|a| a.as_ref().into()).collect();
+        let mut builder = Self::untargeted(api_key, project, validate_only);
+        builder.token(token);
+        builder
+    }
 
-        FCMRequestBuilder {
-            api_key,
-            project,
-            validate_only,
-            message: MessageBuilder {
-                topic: None,
-                registration_ids: Some(converted),
-                collapse_key: None,
-                priority: None,
-                content_available: None,
-                delay_while_idle: None,
-                time_to_live: None,
-                restricted_package_name: None,
-                data: None,
-                notification: None,
-                mutable_content: None,
-            },
-        }
+    /// Get a new instance of FCMRequest, sent to the devices subscribed to
+    /// `topic`.
+    pub fn to_topic(api_key: &'a str, project: &'a str, topic: &'a str, validate_only: Option<bool>) -> Self {
+        let mut builder = Self::untargeted(api_key, project, validate_only);
+        builder.message.topic = Some(topic);
+        builder
+    }
+
+    /// Get a new instance of FCMRequest, sent to the devices subscribed to
+    /// the topics matching `condition`, e.g. `'dogs' in topics && 'cats' in
+    /// topics`.
+    pub fn to_condition(api_key: &'a str, project: &'a str, condition: &'a str, validate_only: Option<bool>) -> Self {
+        let mut builder = Self::untargeted(api_key, project, validate_only);
+        builder.condition(condition);
+        builder
+    }
+
+    /// Get a new instance of FCMRequest, sent to the devices of the
+    /// registration tokens `ids`.
+    pub fn to_tokens<I>(api_key: &'a str, project: &'a str, ids: I, validate_only: Option<bool>) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoRegistrationId<'a>,
+    {
+        let mut builder = Self::untargeted(api_key, project, validate_only);
+        builder.registration_ids(ids);
+        builder
+    }
+
+    /// Get a new instance of FCMRequest. You need to supply topic.
+    #[deprecated(note = "use `FCMRequestBuilder::to_topic` instead")]
+    pub fn new(api_key: &'a str, project: &'a str, topic: &'a str, validate_only: Option<bool>) -> Self {
+        Self::to_topic(api_key, project, topic, validate_only)
+    }
+
+    /// Get a new instance of FCMRequest, sent to the single device of the
+    /// registration `token`.
+    #[deprecated(note = "use `FCMRequestBuilder::to_token` instead")]
+    pub fn new_token<T>(api_key: &'a str, project: &'a str, token: T, validate_only: Option<bool>) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        Self::to_token(api_key, project, token, validate_only)
+    }
+
+    /// Get a new instance of FCMRequest, sent to the devices subscribed to
+    /// the topics matching `condition`.
+    #[deprecated(note = "use `FCMRequestBuilder::to_condition` instead")]
+    pub fn new_condition(api_key: &'a str, project: &'a str, condition: &'a str, validate_only: Option<bool>) -> Self {
+        Self::to_condition(api_key, project, condition, validate_only)
     }
 
-    /// String value to replace format specifiers in the body string.
-    pub fn registration_ids<S>(&mut self, ids: &'a [S]) -> &mut Self
+    /// Get a new instance of FCMRequest. You need to supply registration ids.
+    #[deprecated(note = "use `FCMRequestBuilder::to_tokens` instead")]
+    pub fn new_multi<I>(api_key: &'a str, project: &'a str, ids: I, validate_only: Option<bool>) -> Self
     where
-        S: Into<Cow<'a, str>> + AsRef<str>,
+        I: IntoIterator,
+        I::Item: IntoRegistrationId<'a>,
     {
-        let converted = ids.iter().map(|a| a.as_ref().into()).collect();
+        Self::to_tokens(api_key, project, ids, validate_only)
+    }
+
+    /// Set the registration ids to send the message to, instead of its topic,
+    /// token or condition. Owned strings are moved into the message, borrowed
+    /// ones are not copied.
+    pub fn registration_ids<I>(&mut self, ids: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: IntoRegistrationId<'a>,
+    {
+        let converted = ids.into_iter().map(IntoRegistrationId::into_registration_id).collect();
 
         self.message.registration_ids = Some(converted);
+        self.message.condition = None;
+        self.message.token = None;
+        self.message.topic = None;
+        self
+    }
+
+    /// Set the registration token of the single device to send the message
+    /// to, instead of its topic, registration ids or condition.
+    pub fn token<T>(&mut self, token: T) -> &mut Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        self.message.token = Some(token.into());
+        self.message.condition = None;
+        self.message.registration_ids = None;
+        self.message.topic = None;
+        self
+    }
+
+    /// Send the message to the devices subscribed to the topics matching the
+    /// `condition` expression, instead of its topic, token or registration
+    /// ids.
+    pub fn condition(&mut self, condition: &'a str) -> &mut Self {
+        self.message.condition = Some(condition);
+        self.message.registration_ids = None;
+        self.message.token = None;
+        self.message.topic = None;
         self
     }
 
-    /// Set this parameter to identify groups of messages that can be collapsed.
+    /// Set this parameter to identify groups of messages that can be collapsed,
+    /// as `android.collapse_key`.
     pub fn collapse_key(&mut self, collapse_key: &'a str) -> &mut Self {
-        self.message.collapse_key = Some(collapse_key);
+        self.message
+            .android
+            .get_or_insert_with(AndroidConfig::default)
+            .collapse_key = Some(collapse_key);
         self
     }
 
-    /// Set the priority of the message. You can set Normal or High priorities.
+    /// Set the priority of the message, as `android.priority`. You can set
+    /// Normal or High priorities.
     /// # Examples:
     /// ```rust
-    /// use fcm::{FCMRequestBuilder, Priority};
+    /// use fcm_http1::{FCMRequestBuilder, Priority};
     ///
-    /// let mut builder = FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", None);
+    /// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
     /// builder.priority(Priority::High);
     /// let message = builder.finalize();
     /// ```
     pub fn priority(&mut self, priority: Priority) -> &mut Self {
-        self.message.priority = Some(priority);
+        self.message.android.get_or_insert_with(AndroidConfig::default).priority = Some(priority);
+        self
+    }
+
+    /// Make the message a silent one clearing the badge of the app: a
+    /// background notification with a badge of 0 on iOS, sent with the
+    /// `apns-push-type: background` and `apns-priority: 5` headers, and a
+    /// data message with `well_known::CLEAR_BADGE` set to `true` on Android,
+    /// for the app to clear its badge. `try_finalize` fails if the message also has a
+    /// notification or an APNs alert to show.
+    pub fn clear_badge(&mut self) -> &mut Self {
+        self.message.clear_badge = true;
         self
     }
 
-    /// To set the `content-available` field on iOS
+    /// To set the `content-available` field on iOS, as
+    /// `apns.payload.aps.content-available`.
     pub fn content_available(&mut self, content_available: bool) -> &mut Self {
         self.message.content_available = Some(content_available);
         self
     }
 
     /// When set to `true`, sends the message only when the device is active.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn delay_while_idle(&mut self, delay_while_idle: bool) -> &mut Self {
         self.message.delay_while_idle = Some(delay_while_idle);
         self
     }
 
     /// How long (in seconds) to keep the message on FCM servers in case the device
-    /// is offline. The maximum and default is 4 weeks.
+    /// is offline. The maximum and default is 4 weeks. Same as `ttl`, negative
+    /// values are clamped to zero.
     pub fn time_to_live(&mut self, time_to_live: i32) -> &mut Self {
-        self.message.time_to_live = Some(time_to_live);
+        self.ttl(Duration::from_secs(time_to_live.max(0) as u64))
+    }
+
+    /// How long to keep the message on FCM servers in case the device is
+    /// offline, set as `android.ttl`. Durations longer than the maximum and
+    /// default of 4 weeks are clamped to it.
+    pub fn ttl(&mut self, ttl: Duration) -> &mut Self {
+        let mut builder = AndroidConfigBuilder::new();
+        builder.ttl(ttl);
+
+        self.message.android.get_or_insert_with(AndroidConfig::default).ttl = builder.finalize().ttl;
         self
     }
 
-    /// Package name of the application where the registration tokens must match.
+    /// Set the Android specific options of the message, replacing the ones
+    /// previously set.
+    pub fn android(&mut self, android: AndroidConfig<'a>) -> &mut Self {
+        self.message.android = Some(android);
+        self
+    }
+
+    /// Package name of the application where the registration tokens must
+    /// match, as `android.restricted_package_name`.
     pub fn restricted_package_name(&mut self, restricted_package_name: &'a str) -> &mut Self {
-        self.message.restricted_package_name = Some(restricted_package_name);
+        self.message
+            .android
+            .get_or_insert_with(AndroidConfig::default)
+            .restricted_package_name = Some(restricted_package_name);
         self
     }
 
     /// Use this to add custom key-value pairs to the message. This data
     /// must be handled appropriately on the client end. The data can be
-    /// anything that Serde can serialize to JSON.
+    /// anything that Serde can serialize to a JSON map, whose keys are not
+    /// reserved (see `well_known::is_reserved`). FCM only accepts string
+    /// values, the other ones are converted to their JSON text, as
+    /// `AndroidConfigBuilder::data` does.
     ///
     /// # Examples:
     /// ```rust
-    /// use fcm::FCMRequestBuilder;
+    /// use fcm_http1::FCMRequestBuilder;
     /// use std::collections::HashMap;
     ///
     /// let mut map = HashMap::new();
     /// map.insert("message", "Howdy!");
     ///
-    /// let mut builder = FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", None);
+    /// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
     /// builder.data(&map);
     /// let message = builder.finalize();
     /// ```
-    pub fn data(&mut self, data: &dyn erased_serde::Serialize) -> Result<&mut Self, serde_json::Error> {
-        self.message.data = Some(serde_json::to_value(data)?);
+    pub fn data(&mut self, data: &dyn erased_serde::Serialize) -> Result<&mut Self, BuildError> {
+        let data = string_data("data", data)?;
+
+        self.message.data = Some(data.map(|(key, value)| (key, Value::String(value))).collect());
         Ok(self)
     }
 
+    /// Use this to set the custom key-value pairs of the message from an
+    /// already built JSON map. Its keys and values are moved into the message
+    /// without being serialized again nor copied, only the map holding them
+    /// is rebuilt, sorted. FCM only accepts string values and keys which are
+    /// not reserved (see `well_known::is_reserved`), which is checked in a
+    /// single pass over the map.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// use fcm_http1::FCMRequestBuilder;
+    /// use serde_json::{Map, Value};
+    ///
+    /// let mut map = Map::new();
+    /// map.insert("message".to_string(), Value::from("Howdy!"));
+    ///
+    /// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+    /// builder.data_map(map).unwrap();
+    /// let message = builder.finalize();
+    /// ```
+    pub fn data_map(&mut self, data: Map<String, Value>) -> Result<&mut Self, BuildError> {
+        for (key, value) in &data {
+            check_data_key("data", key)?;
+
+            if !value.is_string() {
+                return Err(BuildError::validation(
+                    "data",
+                    format!("the value of key `{}` is not a string", key),
+                ));
+            }
+        }
+
+        Ok(self.data_map_unchecked(data))
+    }
+
+    /// Same as `data_map`, without checking the values of the map. The caller
+    /// must make sure every value is a string, otherwise FCM rejects the
+    /// message. This is only checked in debug builds.
+    pub fn data_map_unchecked(&mut self, data: Map<String, Value>) -> &mut Self {
+        debug_assert!(data.values().all(Value::is_string), "data values must be strings");
+
+        self.message.data = Some(data.into_iter().collect());
+        self
+    }
+
     /// Use this to set a `Notification` for the message.
     /// # Examples:
     /// ```rust
-    /// use fcm::{FCMRequestBuilder, NotificationBuilder};
+    /// use fcm_http1::{FCMRequestBuilder, NotificationBuilder};
     ///
     /// let mut builder = NotificationBuilder::new();
     /// builder.title("Hey!");
     /// builder.body("Do you want to catch up later?");
     /// let notification = builder.finalize();
     ///
-    /// let mut builder = FCMRequestBuilder::new("<FCM API Key>", "<project>", "<registration id>", None);
+    /// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
     /// builder.notification(notification);
     /// let message = builder.finalize();
     /// ```
@@ -253,33 +662,534 @@ impl<'a> FCMRequestBuilder<'a> {
         self
     }
 
-    /// To set the `mutable_content` field on iOS
+    /// To set the `mutable-content` field on iOS, as
+    /// `apns.payload.aps.mutable-content`.
     pub fn mutable_content(&mut self, mutable_content: bool) -> &mut Self {
         self.message.mutable_content = Some(mutable_content);
         self
     }
 
+    /// Set the APNs specific options of the message, replacing the ones
+    /// previously set. The `content_available` and `mutable_content` flags
+    /// are added to its payload rather than replaced by it.
+    pub fn apns(&mut self, apns: ApnsConfig<'a>) -> &mut Self {
+        self.message.apns = Some(apns);
+        self
+    }
+
+    /// Set the FCM options of the message, for all the platforms. The
+    /// Android and APNs configs have their own.
+    pub fn fcm_options(&mut self, fcm_options: FcmOptions<'a>) -> &mut Self {
+        self.message.fcm_options = Some(fcm_options);
+        self
+    }
+
+    /// When set to `true`, nulls, empty objects and empty arrays are removed
+    /// from the message before sending it. Some fields, like an empty
+    /// `notification`, change how FCM handles the message even when empty.
+    /// # Examples:
+    /// ```rust
+    /// use fcm_http1::{FCMRequestBuilder, NotificationBuilder};
+    ///
+    /// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+    /// builder.notification(NotificationBuilder::new().finalize());
+    /// builder.prune_empty(true);
+    /// let message = builder.finalize();
+    /// ```
+    pub fn prune_empty(&mut self, prune_empty: bool) -> &mut Self {
+        self.prune_empty = prune_empty;
+        self
+    }
+
+    /// Keep the field at `path` when removing empty fields with `prune_empty`,
+    /// even if it is empty. The path is relative to the message, with the keys
+    /// separated by dots, e.g. `notification`.
+    pub fn preserve_empty(&mut self, path: &'a str) -> &mut Self {
+        self.preserve_empty.push(path);
+        self
+    }
+
+    /// Set what `try_finalize` does with the string fields set to an empty
+    /// string: the topic and condition, the analytics labels, the collapse
+    /// key and restricted package name, and the strings of the
+    /// notifications. `EmptyStrings::Reject` by default. `finalize` always
+    /// sends them as is.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// use fcm_http1::{AndroidConfigBuilder, EmptyStrings, FCMRequestBuilder};
+    ///
+    /// let mut android = AndroidConfigBuilder::new();
+    /// android.collapse_key("");
+    ///
+    /// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "my-project", "<registration token>", None);
+    /// builder.android(android.finalize()).empty_strings(EmptyStrings::TreatAsUnset);
+    /// let message = builder.try_finalize().unwrap();
+    /// ```
+    pub fn empty_strings(&mut self, empty_strings: EmptyStrings) -> &mut Self {
+        self.empty_strings = empty_strings;
+        self
+    }
+
+    /// Same as `empty_strings`, for the field at `path` only, e.g.
+    /// `notification.body` for a notification whose body can be empty. The
+    /// path is relative to the message, with the keys separated by dots.
+    pub fn empty_strings_for(&mut self, path: &'a str, empty_strings: EmptyStrings) -> &mut Self {
+        self.empty_strings_for.push((path, empty_strings));
+        self
+    }
+
+    /// Same as `finalize`, but fails when the project is not a Google Cloud
+    /// project id, when a string field is empty, depending on
+    /// `empty_strings`, or unless exactly one of the token, topic, condition and
+    /// registration ids targets is set. An empty list of registration ids is
+    /// not a target. Also fails when the color of the Android notification is
+    /// not in #rrggbb format, when it has both a custom and the default sound,
+    /// when a message clearing the badge has something to show, or when the
+    /// APNs payload is larger than APNs accepts: 4096 bytes, or 5120 bytes
+    /// for a VoIP notification.
+    pub fn try_finalize(mut self) -> Result<FCMRequest<'a>, BuildError> {
+        if !is_valid_project_id(self.project) {
+            return Err(BuildError::validation(
+                "project",
+                format!("`{}` is not a Google Cloud project id", self.project),
+            ));
+        }
+
+        for (field, value) in self.message.string_fields() {
+            if *value != Some("") {
+                continue;
+            }
+
+            // The last policy set for the field wins.
+            let empty_strings = self
+                .empty_strings_for
+                .iter()
+                .rev()
+                .find(|(path, _)| *path == field)
+                .map_or(self.empty_strings, |(_, empty_strings)| *empty_strings);
+
+            match empty_strings {
+                EmptyStrings::Reject => return Err(BuildError::validation(field, "empty string")),
+                EmptyStrings::TreatAsUnset => *value = None,
+                EmptyStrings::Allow => {}
+            }
+        }
+
+        let targets: Vec<_> = [
+            ("token", self.message.token.is_some()),
+            ("topic", self.message.topic.is_some()),
+            ("condition", self.message.condition.is_some()),
+            (
+                "registration_ids",
+                self.message
+                    .registration_ids
+                    .as_ref()
+                    .is_some_and(|ids| !ids.is_empty()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+
+        match targets.len() {
+            0 => return Err(BuildError::MissingTarget),
+            1 => {}
+            _ => return Err(BuildError::Conflict { fields: targets }),
+        }
+
+        for (field, fcm_options) in [
+            ("fcm_options.analytics_label", self.message.fcm_options.as_ref()),
+            (
+                "android.fcm_options.analytics_label",
+                self.message.android.as_ref().and_then(AndroidConfig::fcm_options),
+            ),
+            (
+                "apns.fcm_options.analytics_label",
+                self.message.apns.as_ref().and_then(ApnsConfig::fcm_options),
+            ),
+        ] {
+            if let Some(label) = fcm_options
+                .and_then(FcmOptions::analytics_label)
+                .filter(|label| !is_valid_analytics_label(label))
+            {
+                return Err(BuildError::validation(
+                    field,
+                    format!("`{}` is not 1 to 50 characters among [a-zA-Z0-9-_.~%]", label),
+                ));
+            }
+        }
+
+        let android_notification = self
+            .message
+            .android
+            .as_ref()
+            .and_then(|android| android.notification.as_ref());
+
+        if let Some(color) = android_notification
+            .and_then(|notification| notification.color())
+            .filter(|color| !is_valid_color(color))
+        {
+            return Err(BuildError::validation(
+                "android.notification.color",
+                format!("`{}` is not in #rrggbb format", color),
+            ));
+        }
+
+        #[cfg(feature = "url")]
+        if let Some(image) = android_notification
+            .and_then(|notification| notification.image())
+            .filter(|image| !is_http_url(image))
+        {
+            return Err(BuildError::validation(
+                "android.notification.image",
+                format!("`{}` is not an http(s) URL", image),
+            ));
+        }
+
+        if android_notification
+            .is_some_and(|notification| notification.sound().is_some() && notification.default_sound() == Some(true))
+        {
+            return Err(BuildError::Conflict {
+                fields: vec!["android.notification.sound", "android.notification.default_sound"],
+            });
+        }
+
+        let has_alert = self.message.notification.is_some()
+            || self
+                .message
+                .android
+                .as_ref()
+                .is_some_and(|android| android.notification.is_some())
+            || self.message.apns.as_ref().is_some_and(ApnsConfig::has_alert);
+
+        if self.message.clear_badge && has_alert {
+            return Err(BuildError::PolicyViolation(
+                "a message clearing the badge cannot have a notification nor an alert".to_string(),
+            ));
+        }
+
+        let request = self.finalize();
+
+        // Once finalized, with the keys `finalize` adds to the `aps` dictionary.
+        if let Some(apns) = request.body.message.apns.as_ref() {
+            let limit = apns.max_payload_bytes();
+            let size = match apns.payload() {
+                Some(payload) => serde_json::to_vec(payload)?.len(),
+                None => 0,
+            };
+
+            if size > limit {
+                return Err(BuildError::PayloadTooLarge {
+                    field: "apns.payload",
+                    limit,
+                    size,
+                });
+            }
+        }
+
+        Ok(request)
+    }
+
     /// Complete the build and get a `FCMRequest` instance
     pub fn finalize(self) -> FCMRequest<'a> {
+        let apns = match (
+            self.message.apns,
+            self.message.content_available,
+            self.message.mutable_content,
+        ) {
+            (apns, None, None) => apns,
+            (apns, content_available, mutable_content) => Some(
+                apns.unwrap_or_default()
+                    .with_aps_flags(content_available, mutable_content),
+            ),
+        };
+
+        let mut data = self.message.data;
+
+        let apns = if self.message.clear_badge {
+            data.get_or_insert_with(BTreeMap::new)
+                .insert(well_known::CLEAR_BADGE.to_string(), Value::from("true"));
+
+            Some(apns.unwrap_or_default().with_badge_cleared())
+        } else {
+            apns
+        };
+
         FCMRequest {
             api_key: self.api_key,
             project: self.project,
+            prune_empty: self.prune_empty,
+            preserve_empty: self.preserve_empty,
+            clear_badge: self.message.clear_badge,
+            empty_strings: self.empty_strings,
+            empty_strings_for: self.empty_strings_for,
             body: MessageBody {
                 message: Message {
+                    condition: self.message.condition,
+                    token: self.message.token,
                     topic: self.message.topic,
                     registration_ids: self.message.registration_ids,
-                    collapse_key: self.message.collapse_key,
-                    priority: self.message.priority,
-                    content_available: self.message.content_available,
+                    apns,
+                    #[cfg(feature = "legacy-http")]
                     delay_while_idle: self.message.delay_while_idle,
-                    time_to_live: self.message.time_to_live,
-                    restricted_package_name: self.message.restricted_package_name,
-                    data: self.message.data.clone(),
+                    fcm_options: self.message.fcm_options,
+                    android: self.message.android,
+                    data,
                     notification: self.message.notification,
-                    mutable_content: self.message.mutable_content,
                 },
                 validate_only: self.validate_only.unwrap_or(false),
             },
         }
     }
 }
+
+/// The `fcm_options` of a message, or of its Android or APNs config. Use
+/// the corresponding `FcmOptionsBuilder` to get an instance.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy, Default)]
+pub struct FcmOptions<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) analytics_label: Option<&'a str>,
+}
+
+impl<'a> FcmOptions<'a> {
+    /// The label of the message in the FCM analytics.
+    pub fn analytics_label(&self) -> Option<&'a str> {
+        self.analytics_label
+    }
+}
+
+/// A builder to get a `FcmOptions` instance.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{FCMRequestBuilder, FcmOptionsBuilder};
+///
+/// let mut fcm_options = FcmOptionsBuilder::new();
+/// fcm_options.analytics_label("campaign-42");
+///
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "my-project", "<registration token>", None);
+/// builder.fcm_options(fcm_options.finalize());
+/// let message = builder.try_finalize().unwrap();
+/// ```
+#[derive(Default)]
+pub struct FcmOptionsBuilder<'a> {
+    fcm_options: FcmOptions<'a>,
+}
+
+impl<'a> FcmOptionsBuilder<'a> {
+    /// Get a new `FcmOptionsBuilder` instance, with no options set.
+    pub fn new() -> FcmOptionsBuilder<'a> {
+        Self::default()
+    }
+
+    /// Set the label of the message in the FCM analytics, to find it in the
+    /// aggregated delivery data. FCM accepts 1 to 50 characters among
+    /// `[a-zA-Z0-9-_.~%]`, which `FCMRequestBuilder::try_finalize` checks.
+    pub fn analytics_label(&mut self, analytics_label: &'a str) -> &mut Self {
+        self.fcm_options.analytics_label = Some(analytics_label);
+        self
+    }
+
+    /// Complete the build and get a `FcmOptions` instance
+    pub fn finalize(self) -> FcmOptions<'a> {
+        self.fcm_options
+    }
+}
+
+/// The entries of the data at `field`, which must serialize to a map whose
+/// keys are not reserved, the values which are not strings converted to
+/// their JSON text.
+pub(crate) fn string_data(
+    field: &'static str,
+    data: &dyn erased_serde::Serialize,
+) -> Result<impl Iterator<Item = (String, String)>, BuildError> {
+    let data = match serde_json::to_value(data)? {
+        Value::Object(data) => data,
+        _ => return Err(BuildError::validation(field, "not a map")),
+    };
+
+    check_data_keys(field, &data)?;
+
+    Ok(data.into_iter().map(|(key, value)| match value {
+        Value::String(value) => (key, value),
+        value => (key, value.to_string()),
+    }))
+}
+
+/// Check none of the keys of the data at `field` is reserved by FCM.
+pub(crate) fn check_data_keys(field: &'static str, data: &Map<String, Value>) -> Result<(), BuildError> {
+    data.keys().try_for_each(|key| check_data_key(field, key))
+}
+
+/// Check `key` of the data at `field` is not reserved by FCM.
+pub(crate) fn check_data_key(field: &'static str, key: &str) -> Result<(), BuildError> {
+    if well_known::is_reserved(key) {
+        return Err(BuildError::validation(field, format!("the key `{}` is reserved", key)));
+    }
+
+    Ok(())
+}
+
+fn is_valid_analytics_label(label: &str) -> bool {
+    (1..=50).contains(&label.len())
+        && label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.~%".contains(&b))
+}
+
+/// Check `project` is a Google Cloud project id, optionally prefixed with a
+/// domain for domain-scoped projects (e.g. `example.com:my-project`), so it
+/// cannot alter the path of the request.
+pub(crate) fn is_valid_project_id(project: &str) -> bool {
+    let (domain, id) = match project.rsplit_once(':') {
+        Some((domain, id)) => (Some(domain), id),
+        None => (None, project),
+    };
+
+    let valid_domain = domain.map_or(true, |domain| {
+        !domain.is_empty()
+            && domain
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'.')
+    });
+
+    let valid_id = (6..=30).contains(&id.len())
+        && id.starts_with(|c: char| c.is_ascii_lowercase())
+        && !id.ends_with('-')
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+
+    valid_domain && valid_id
+}
+
+#[cfg(feature = "url")]
+fn is_http_url(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Why a builder could not build a message or one of its parts. Every
+/// fallible builder method returns it.
+#[derive(PartialEq, Debug, Clone)]
+pub enum BuildError {
+    /// A value could not be serialized to JSON. Holds the error of
+    /// `serde_json`.
+    Serialization(String),
+
+    /// The value of a field is not accepted by FCM. Holds the path of the
+    /// field in the message, e.g. `android.notification.color`, and why.
+    Validation { field: &'static str, reason: String },
+
+    /// Several fields are set which FCM accepts only one of. Holds their
+    /// paths, e.g. the targets `token` and `topic`.
+    Conflict { fields: Vec<&'static str> },
+
+    /// None of the token, topic, condition and registration ids targets is
+    /// set.
+    MissingTarget,
+
+    /// The fields are valid on their own but break a rule of the kind of
+    /// message being built, e.g. a message clearing the badge showing an
+    /// alert. Holds why.
+    PolicyViolation(String),
+
+    /// A part of the message is larger than its platform accepts. Holds the
+    /// path of the field, e.g. `apns.payload`, the limit and the size of the
+    /// field, in bytes.
+    PayloadTooLarge {
+        field: &'static str,
+        limit: usize,
+        size: usize,
+    },
+}
+
+/// The former name of `BuildError`.
+#[deprecated(note = "renamed to `BuildError`")]
+pub type MessageBuildError = BuildError;
+
+impl BuildError {
+    pub(crate) fn validation(field: &'static str, reason: impl Into<String>) -> BuildError {
+        BuildError::Validation {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Serialization(ref error) => write!(f, "cannot serialize the message: {}", error),
+            BuildError::Validation { field, ref reason } => write!(f, "invalid `{}`: {}", field, reason),
+            BuildError::Conflict { ref fields } => {
+                write!(f, "these fields cannot be set together: {}", fields.join(", "))
+            }
+            BuildError::MissingTarget => write!(f, "the message has no target"),
+            BuildError::PolicyViolation(ref reason) => write!(f, "{}", reason),
+            BuildError::PayloadTooLarge { field, limit, size } => {
+                write!(f, "`{}` is {} bytes, over the limit of {} bytes", field, size, limit)
+            }
+        }
+    }
+}
+
+impl From<serde_json::Error> for BuildError {
+    fn from(error: serde_json::Error) -> BuildError {
+        BuildError::Serialization(error.to_string())
+    }
+}
+
+/// A builder to get the data map of a message, with helpers for the keys the
+/// Firebase client SDKs interpret.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{DataMapBuilder, FCMRequestBuilder};
+///
+/// let mut data = DataMapBuilder::new();
+/// data.click_action("/matches/42").channel_hint("scores");
+/// data.insert("score", "2-1");
+///
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+/// builder.data_map(data.finalize()).unwrap();
+/// ```
+#[derive(Default, Debug)]
+pub struct DataMapBuilder {
+    data: Map<String, Value>,
+}
+
+impl DataMapBuilder {
+    /// Get a new, empty `DataMapBuilder` instance.
+    pub fn new() -> DataMapBuilder {
+        Self::default()
+    }
+
+    /// Set the value of a custom key.
+    pub fn insert(&mut self, key: &str, value: &str) -> &mut Self {
+        self.data.insert(key.to_string(), Value::from(value));
+        self
+    }
+
+    /// The action or route the app opens when the user taps the
+    /// notification, set as `well_known::CLICK_ACTION`.
+    pub fn click_action(&mut self, route: &str) -> &mut Self {
+        self.insert(well_known::CLICK_ACTION, route)
+    }
+
+    /// The Android notification channel the app should use, set as
+    /// `well_known::ANDROID_CHANNEL_ID`.
+    pub fn channel_hint(&mut self, id: &str) -> &mut Self {
+        self.insert(well_known::ANDROID_CHANNEL_ID, id)
+    }
+
+    /// Complete the build and get the data map, to be given to
+    /// `FCMRequestBuilder::data_map`.
+    pub fn finalize(self) -> Map<String, Value> {
+        self.data
+    }
+}
@@ -1,8 +1,18 @@
 use crate::notification::NotificationBuilder;
-use crate::{FCMRequestBuilder, Priority};
+use crate::{well_known, BuildError, DataMapBuilder, EmptyStrings, FCMRequestBuilder, FcmOptions, FcmOptionsBuilder};
+use crate::{AndroidConfigBuilder, AndroidNotificationBuilder, ApnsConfigBuilder, Priority, Sound};
 use serde::Serialize;
-use serde_json::json;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+thread_local! {
+    /// How many times a message was serialized on the current thread.
+    pub(crate) static MESSAGE_SERIALIZATIONS: Cell<usize> = const { Cell::new(0) };
+}
 
 #[derive(Serialize)]
 struct CustomData {
@@ -12,14 +22,14 @@ struct CustomData {
 
 #[test]
 fn should_create_new_message() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
     assert_eq!(msg.body.message.topic, Some("token"));
 }
 
 #[test]
 fn should_leave_nones_out_of_the_json() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
     let payload = serde_json::to_string(&msg.body).unwrap();
 
     let expected_payload = json!({
@@ -37,7 +47,7 @@ fn should_leave_nones_out_of_the_json() {
 
 #[test]
 fn should_add_custom_data_to_the_payload() {
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
 
     let data = CustomData { foo: "bar", bar: false };
 
@@ -50,7 +60,7 @@ fn should_add_custom_data_to_the_payload() {
         "message": {
             "data": {
                 "foo": "bar",
-                "bar": false,
+                "bar": "false",
             },
             "topic": "token"
         },
@@ -65,14 +75,16 @@ fn should_add_custom_data_to_the_payload() {
 
 #[test]
 fn should_be_able_to_render_a_full_message_to_json() {
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    let mut android = AndroidConfigBuilder::new();
+    android.direct_boot_ok(true);
 
     builder
         .registration_ids(&["one", "two"])
+        .android(android.finalize())
         .collapse_key("foo")
         .priority(Priority::High)
         .content_available(false)
-        .delay_while_idle(true)
         .time_to_live(420)
         .restricted_package_name("pkg")
         .notification(NotificationBuilder::new().finalize());
@@ -81,14 +93,19 @@ fn should_be_able_to_render_a_full_message_to_json() {
 
     let expected_payload = json!({
         "message": {
-            "topic": "token",
             "registration_ids": ["one", "two"],
-            "collapse_key": "foo",
-            "priority": "high",
-            "content_available": false,
-            "delay_while_idle": true,
-            "time_to_live": 420,
-            "restricted_package_name": "pkg",
+            "apns": {
+                "payload": {
+                    "aps": {"content-available": 0},
+                },
+            },
+            "android": {
+                "collapse_key": "foo",
+                "direct_boot_ok": true,
+                "priority": "HIGH",
+                "restricted_package_name": "pkg",
+                "ttl": "420s",
+            },
             "notification": {},
         },
         "validate_only": false
@@ -102,126 +119,1450 @@ fn should_be_able_to_render_a_full_message_to_json() {
 
 #[test]
 fn should_set_registration_ids() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
     assert_eq!(msg.body.message.registration_ids, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.registration_ids(&["id1"]);
     let msg = builder.finalize();
 
     assert_eq!(msg.body.message.registration_ids, Some(vec![Cow::from("id1")]));
 }
 
+#[test]
+fn should_send_to_a_single_token() {
+    let msg = FCMRequestBuilder::to_token("api_key", "project", "device-token", None).finalize();
+    let payload = serde_json::to_value(&msg.body).unwrap();
+
+    assert_eq!(json!({"token": "device-token"}), payload["message"]);
+
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "topic", None).finalize();
+    let payload = serde_json::to_value(&msg.body).unwrap();
+
+    assert_eq!(None, payload["message"].get("token"));
+}
+
+#[test]
+fn should_never_serialize_several_targets() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "topic", None);
+    builder.token(String::from("device-token"));
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"token": "device-token"}), payload["message"]);
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "device-token", None);
+    builder.registration_ids(["one", "two"]);
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"registration_ids": ["one", "two"]}), payload["message"]);
+
+    let mut builder = FCMRequestBuilder::to_tokens("api_key", "project", ["one", "two"], None);
+    builder.token("device-token");
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"token": "device-token"}), payload["message"]);
+}
+
+#[test]
+fn should_send_to_a_condition() {
+    let condition = "'dogs' in topics && 'cats' in topics";
+
+    let msg = FCMRequestBuilder::to_condition("api_key", "project", condition, None).finalize();
+    let payload = serde_json::to_value(&msg.body).unwrap();
+
+    assert_eq!(json!({"condition": condition}), payload["message"]);
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "topic", None);
+    builder.token("device-token").condition(condition);
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"condition": condition}), payload["message"]);
+
+    let mut builder = FCMRequestBuilder::to_condition("api_key", "project", condition, None);
+    builder.token("device-token");
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"token": "device-token"}), payload["message"]);
+}
+
+#[test]
+fn should_finalize_a_message_with_a_single_target() {
+    let builders = vec![
+        FCMRequestBuilder::to_topic("api_key", "project", "topic", None),
+        FCMRequestBuilder::to_token("api_key", "project", "token", None),
+        FCMRequestBuilder::to_condition("api_key", "project", "'dogs' in topics", None),
+        FCMRequestBuilder::to_tokens("api_key", "project", ["one", "two"], None),
+    ];
+
+    for builder in builders {
+        assert!(builder.try_finalize().is_ok());
+    }
+}
+
+#[test]
+fn should_not_finalize_a_message_without_target() {
+    let builder = FCMRequestBuilder::to_tokens("api_key", "project", Vec::<String>::new(), None);
+
+    assert_eq!(Some(BuildError::MissingTarget), builder.try_finalize().err());
+}
+
+#[test]
+fn should_not_finalize_a_message_to_an_invalid_project() {
+    for project in ["short", "my-project/../other", ":my-project"] {
+        let builder = FCMRequestBuilder::to_token("api_key", project, "token", None);
+
+        assert_eq!(
+            Some(BuildError::Validation {
+                field: "project",
+                reason: format!("`{}` is not a Google Cloud project id", project),
+            }),
+            builder.try_finalize().err()
+        );
+    }
+
+    let builder = FCMRequestBuilder::to_token("api_key", "example.com:my-project", "token", None);
+
+    assert!(builder.try_finalize().is_ok());
+}
+
+#[test]
+fn should_not_finalize_a_message_with_conflicting_targets() {
+    let targets = ["token", "topic", "condition", "registration_ids"];
+
+    for (i, first) in targets.iter().enumerate() {
+        for second in &targets[i + 1..] {
+            let mut builder = FCMRequestBuilder::to_tokens("api_key", "project", Vec::<String>::new(), None);
+
+            for target in [first, second] {
+                match *target {
+                    "token" => builder.message.token = Some(Cow::from("token")),
+                    "topic" => builder.message.topic = Some("topic"),
+                    "condition" => builder.message.condition = Some("'dogs' in topics"),
+                    _ => builder.message.registration_ids = Some(vec![Cow::from("one")]),
+                }
+            }
+
+            assert_eq!(
+                Some(BuildError::Conflict {
+                    fields: vec![first, second]
+                }),
+                builder.try_finalize().err()
+            );
+        }
+    }
+}
+
+#[test]
+fn should_populate_the_target_of_each_constructor() {
+    let builders = vec![
+        (
+            FCMRequestBuilder::to_token("api_key", "project", "a", None),
+            json!({"token": "a"}),
+        ),
+        (
+            FCMRequestBuilder::to_topic("api_key", "project", "a", None),
+            json!({"topic": "a"}),
+        ),
+        (
+            FCMRequestBuilder::to_condition("api_key", "project", "'a' in topics", None),
+            json!({"condition": "'a' in topics"}),
+        ),
+        (
+            FCMRequestBuilder::to_tokens("api_key", "project", ["a", "b"], None),
+            json!({"registration_ids": ["a", "b"]}),
+        ),
+    ];
+
+    for (builder, expected) in builders {
+        let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+        assert_eq!(expected, payload["message"]);
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn should_keep_the_deprecated_constructors() {
+    let pairs = vec![
+        (
+            FCMRequestBuilder::new("api_key", "project", "a", None),
+            FCMRequestBuilder::to_topic("api_key", "project", "a", None),
+        ),
+        (
+            FCMRequestBuilder::new_multi("api_key", "project", ["a"], None),
+            FCMRequestBuilder::to_tokens("api_key", "project", ["a"], None),
+        ),
+        (
+            FCMRequestBuilder::new_token("api_key", "project", "a", None),
+            FCMRequestBuilder::to_token("api_key", "project", "a", None),
+        ),
+        (
+            FCMRequestBuilder::new_condition("api_key", "project", "'a' in topics", None),
+            FCMRequestBuilder::to_condition("api_key", "project", "'a' in topics", None),
+        ),
+    ];
+
+    for (deprecated, builder) in pairs {
+        assert_eq!(builder.finalize().body, deprecated.finalize().body);
+    }
+}
+
 #[test]
 fn should_set_collapse_key() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
-    assert_eq!(msg.body.message.collapse_key, None);
+    assert_eq!(msg.body.message.android, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.collapse_key("key");
     let msg = builder.finalize();
 
-    assert_eq!(msg.body.message.collapse_key, Some("key"));
+    assert_eq!(msg.body.message.android.unwrap().collapse_key(), Some("key"));
 }
 
 #[test]
 fn should_set_priority() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
-    assert_eq!(msg.body.message.priority, None);
+    assert_eq!(msg.body.message.android, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.priority(Priority::Normal);
     let msg = builder.finalize();
 
-    assert_eq!(msg.body.message.priority, Some(Priority::Normal));
+    assert_eq!(msg.body.message.android.unwrap().priority(), Some(Priority::Normal));
 }
 
 #[test]
 fn should_set_content_available() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
-    assert_eq!(msg.body.message.content_available, None);
+    assert_eq!(msg.body.message.apns, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.content_available(true);
     let msg = builder.finalize();
 
-    assert_eq!(msg.body.message.content_available, Some(true));
+    assert_eq!(
+        Some(&json!({"aps": {"content-available": 1}})),
+        msg.body.message.apns.unwrap().payload()
+    );
 }
 
 #[test]
 fn should_set_mutable_content() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
-    assert_eq!(msg.body.message.mutable_content, None);
+    assert_eq!(msg.body.message.apns, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.mutable_content(true);
     let msg = builder.finalize();
 
-    assert_eq!(msg.body.message.mutable_content, Some(true));
+    assert_eq!(
+        Some(&json!({"aps": {"mutable-content": 1}})),
+        msg.body.message.apns.unwrap().payload()
+    );
 }
 
 #[test]
+fn should_nest_the_apns_headers_in_the_message() {
+    let mut apns = ApnsConfigBuilder::new();
+    apns.header("apns-priority", "5").header("apns-collapse-id", "match-42");
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "scores", None);
+    builder.apns(apns.finalize());
+
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(
+        json!({"apns": {"headers": {"apns-collapse-id": "match-42", "apns-priority": "5"}}, "topic": "scores"}),
+        payload["message"]
+    );
+}
+
+#[test]
+fn should_add_the_aps_flags_to_a_custom_apns_payload() {
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&json!({"aps": {"sound": "default"}, "thread": "42"}))
+        .unwrap();
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder
+        .content_available(true)
+        .apns(apns.finalize())
+        .mutable_content(false);
+
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(
+        json!({
+            "payload": {
+                "aps": {
+                    "sound": "default",
+                    "content-available": 1,
+                    "mutable-content": 0,
+                },
+                "thread": "42",
+            },
+        }),
+        payload["message"]["apns"]
+    );
+    assert_eq!(None, payload["message"].get("content_available"));
+    assert_eq!(None, payload["message"].get("mutable_content"));
+}
+
+#[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_delay_while_idle() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
     assert_eq!(msg.body.message.delay_while_idle, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.delay_while_idle(true);
     let msg = builder.finalize();
 
     assert_eq!(msg.body.message.delay_while_idle, Some(true));
+
+    let payload = serde_json::to_value(&msg.body).unwrap();
+
+    assert_eq!(json!(true), payload["message"]["delay_while_idle"]);
 }
 
 #[test]
 fn should_set_time_to_live() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
-    assert_eq!(msg.body.message.time_to_live, None);
+    assert_eq!(msg.body.message.android, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.time_to_live(10);
     let msg = builder.finalize();
 
-    assert_eq!(msg.body.message.time_to_live, Some(10));
+    assert_eq!(msg.body.message.android.unwrap().ttl(), Some(Duration::from_secs(10)));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.time_to_live(-10);
+    let msg = builder.finalize();
+
+    assert_eq!(msg.body.message.android.unwrap().ttl(), Some(Duration::ZERO));
+}
+
+#[test]
+fn should_serialize_the_ttl_under_android() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.ttl(Duration::from_millis(1500));
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"ttl": "1.500s"}), payload["message"]["android"]);
+    assert_eq!(None, payload["message"].get("time_to_live"));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.ttl(Duration::from_secs(3_000_000));
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(json!({"ttl": "2419200s"}), payload["message"]["android"]);
 }
 
 #[test]
 fn should_set_restricted_package_name() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
-    assert_eq!(msg.body.message.restricted_package_name, None);
+    assert_eq!(msg.body.message.android, None);
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.restricted_package_name("name");
     let msg = builder.finalize();
 
-    assert_eq!(msg.body.message.restricted_package_name, Some("name"));
+    assert_eq!(
+        msg.body.message.android.unwrap().restricted_package_name(),
+        Some("name")
+    );
 }
 
 #[test]
 fn should_set_dry_run() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", Some(true)).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", Some(true)).finalize();
 
-    assert_eq!(msg.body.validate_only, true);
+    assert!(msg.body.validate_only);
 }
 
 #[test]
 fn should_set_notifications() {
-    let msg = FCMRequestBuilder::new("api_key", "project", "token", None).finalize();
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
 
     assert_eq!(msg.body.message.notification, None);
 
     let nm = NotificationBuilder::new().finalize();
 
-    let mut builder = FCMRequestBuilder::new("api_key", "project", "token", None);
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
     builder.notification(nm);
     let msg = builder.finalize();
 
-    assert!(msg.body.message.notification != None);
+    assert!(msg.body.message.notification.is_some());
+}
+
+#[test]
+fn should_round_trip_a_message_through_its_builder() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", Some(true));
+
+    builder
+        .registration_ids(&["one", "two"])
+        .collapse_key("foo")
+        .priority(Priority::High)
+        .content_available(false)
+        .time_to_live(420)
+        .restricted_package_name("pkg")
+        .mutable_content(true)
+        .notification(NotificationBuilder::new().finalize());
+
+    builder.data(&CustomData { foo: "bar", bar: false }).unwrap();
+
+    let msg = builder.finalize();
+    let round_tripped = msg.to_builder().finalize();
+
+    assert_eq!(msg.api_key, round_tripped.api_key);
+    assert_eq!(msg.project, round_tripped.project);
+    assert_eq!(msg.body, round_tripped.body);
+}
+
+#[test]
+fn should_modify_a_message_through_its_builder() {
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+
+    let mut builder = msg.to_builder();
+    builder.time_to_live(10);
+    let msg = builder.finalize();
+
+    assert_eq!(msg.body.message.topic, Some("token"));
+    assert_eq!(msg.body.message.android.unwrap().ttl(), Some(Duration::from_secs(10)));
+}
+
+#[test]
+fn should_keep_the_badge_clearing_and_the_empty_strings_policy_through_the_builder() {
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder
+        .clear_badge()
+        .empty_strings(EmptyStrings::TreatAsUnset)
+        .empty_strings_for("android.collapse_key", EmptyStrings::Allow);
+    let msg = builder.try_finalize().unwrap();
+
+    assert_eq!(msg.body, msg.to_builder().try_finalize().unwrap().body);
+
+    // Replacing the data and the APNs config still clears the badge.
+    let mut android = AndroidConfigBuilder::new();
+    android.collapse_key("").restricted_package_name("");
+
+    let mut builder = msg.to_builder();
+    builder
+        .data(&json!({"inbox": "42"}))
+        .unwrap()
+        .apns(ApnsConfigBuilder::new().finalize())
+        .android(android.finalize());
+
+    let payload = serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap();
+
+    assert_eq!(
+        json!({"clear_badge": "true", "inbox": "42"}),
+        payload["message"]["data"]
+    );
+    assert_eq!(
+        json!({"badge": 0, "content-available": 1}),
+        payload["message"]["apns"]["payload"]["aps"]
+    );
+    assert_eq!(json!({"collapse_key": ""}), payload["message"]["android"]);
+
+    let mut builder = msg.to_builder();
+    builder.notification(NotificationBuilder::new().finalize());
+
+    assert!(matches!(builder.try_finalize(), Err(BuildError::PolicyViolation(_))));
+}
+
+#[test]
+fn should_add_a_data_map_to_the_payload() {
+    let mut map = serde_json::Map::new();
+    map.insert("foo".to_string(), json!("bar"));
+    map.insert("baz".to_string(), json!("qux"));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.data_map(map).unwrap();
+
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    let expected_payload = json!({
+        "message": {
+            "data": {
+                "foo": "bar",
+                "baz": "qux",
+            },
+            "topic": "token"
+        },
+        "validate_only": false
+    });
+
+    assert_eq!(expected_payload, payload);
+}
+
+#[test]
+fn should_reject_a_data_map_with_non_string_values() {
+    let mut map = serde_json::Map::new();
+    map.insert("foo".to_string(), json!("bar"));
+    map.insert("count".to_string(), json!(1));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    let error = builder.data_map(map).err().unwrap();
+
+    assert!(error.to_string().contains("`count`"));
+    assert_eq!(builder.finalize().body.message.data, None);
+}
+
+#[test]
+fn should_reject_a_data_map_with_reserved_keys() {
+    for key in ["from", "message_type", "google.ttl", "gcm.notification.title"] {
+        let mut map = serde_json::Map::new();
+        map.insert(key.to_string(), json!("bar"));
+
+        let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+        let error = builder.data_map(map).err().unwrap();
+
+        assert!(error.to_string().contains(&format!("`{}` is reserved", key)));
+    }
+}
+
+#[test]
+fn should_report_the_first_invalid_entry_of_a_data_map() {
+    let mut map = serde_json::Map::new();
+    map.insert("count".to_string(), json!(1));
+    map.insert("from".to_string(), json!(2));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    let error = builder.data_map(map.clone()).err().unwrap();
+
+    assert!(error.to_string().contains("the value of key `count` is not a string"));
+
+    map.remove("count");
+    let error = builder.data_map(map).err().unwrap();
+
+    assert!(error.to_string().contains("the key `from` is reserved"));
+}
+
+#[test]
+fn should_convert_the_data_to_a_string_map() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder
+        .data(&json!({"link": "app://matches/42", "score": 2, "live": true, "teams": ["a", "b"]}))
+        .unwrap();
+
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(
+        json!({"link": "app://matches/42", "live": "true", "score": "2", "teams": "[\"a\",\"b\"]"}),
+        payload["message"]["data"]
+    );
+}
+
+#[test]
+fn should_reject_data_with_reserved_keys() {
+    for key in ["from", "message_type", "google.ttl", "gcm.notification.title"] {
+        let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+
+        assert_eq!(
+            Some(BuildError::Validation {
+                field: "data",
+                reason: format!("the key `{}` is reserved", key),
+            }),
+            builder.data(&json!({ key: "bar" })).err()
+        );
+        assert_eq!(builder.finalize().body.message.data, None);
+    }
+}
+
+#[test]
+fn should_build_a_data_map_with_well_known_keys() {
+    let mut data = DataMapBuilder::new();
+    data.click_action(well_known::FLUTTER_NOTIFICATION_CLICK)
+        .channel_hint("scores")
+        .insert("score", "2-1");
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.data_map(data.finalize()).unwrap();
+
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(
+        json!({
+            "click_action": "FLUTTER_NOTIFICATION_CLICK",
+            "android_channel_id": "scores",
+            "score": "2-1",
+        }),
+        payload["message"]["data"]
+    );
+}
+
+#[test]
+fn should_not_reserve_well_known_keys() {
+    for key in [well_known::CLICK_ACTION, well_known::ANDROID_CHANNEL_ID] {
+        assert!(!well_known::is_reserved(key));
+    }
+}
+
+#[test]
+#[cfg(not(debug_assertions))]
+fn should_serialize_an_unchecked_data_map_as_is() {
+    let mut map = serde_json::Map::new();
+    map.insert("count".to_string(), json!(1));
+    map.insert("nested".to_string(), json!({"foo": ["bar"]}));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.data_map_unchecked(map.clone());
+
+    let payload = serde_json::to_vec(&builder.finalize().body).unwrap();
+    let actual_value: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+
+    assert_eq!(
+        Some(&serde_json::Value::Object(map)),
+        actual_value["message"].get("data")
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "data values must be strings")]
+fn should_assert_unchecked_data_map_values_in_debug_builds() {
+    let mut map = serde_json::Map::new();
+    map.insert("count".to_string(), json!(1));
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.data_map_unchecked(map);
+}
+
+#[test]
+fn should_keep_empty_fields_by_default() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.notification(NotificationBuilder::new().finalize());
+
+    let payload: serde_json::Value = serde_json::from_slice(&builder.finalize().payload().unwrap()).unwrap();
+
+    let expected_payload = json!({
+        "message": {
+            "notification": {},
+            "topic": "token"
+        },
+        "validate_only": false
+    });
+
+    assert_eq!(expected_payload, payload);
+}
+
+#[test]
+fn should_prune_empty_fields() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&json!({
+        "empty": {},
+        "nested": {"empty": [], "null": null},
+    }))
+    .unwrap();
+
+    builder
+        .notification(NotificationBuilder::new().finalize())
+        .apns(apns.finalize())
+        .data(&json!({"foo": "bar"}))
+        .unwrap();
+
+    builder.prune_empty(true);
+
+    let payload: serde_json::Value = serde_json::from_slice(&builder.finalize().payload().unwrap()).unwrap();
+
+    let expected_payload = json!({
+        "message": {
+            "data": {
+                "foo": "bar"
+            },
+            "topic": "token"
+        },
+        "validate_only": false
+    });
+
+    assert_eq!(expected_payload, payload);
+}
+
+#[test]
+fn should_prune_all_but_the_preserved_empty_fields() {
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&json!({"empty": {}, "nested": {"empty": {}}})).unwrap();
+
+    builder
+        .notification(NotificationBuilder::new().finalize())
+        .apns(apns.finalize());
+
+    builder
+        .prune_empty(true)
+        .preserve_empty("notification")
+        .preserve_empty("apns.payload.nested.empty");
+
+    let payload: serde_json::Value = serde_json::from_slice(&builder.finalize().payload().unwrap()).unwrap();
+
+    let expected_payload = json!({
+        "message": {
+            "apns": {
+                "payload": {"nested": {"empty": {}}}
+            },
+            "notification": {},
+            "topic": "token"
+        },
+        "validate_only": false
+    });
+
+    assert_eq!(expected_payload, payload);
+}
+
+#[test]
+fn should_serialize_the_body_like_the_message_body() {
+    for validate_only in [false, true] {
+        let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", Some(validate_only));
+
+        builder
+            .priority(Priority::High)
+            .notification(NotificationBuilder::new().finalize())
+            .data(&CustomData { foo: "bar", bar: false })
+            .unwrap();
+
+        let msg = builder.finalize();
+
+        assert_eq!(serde_json::to_vec(&msg.body).unwrap(), msg.payload().unwrap());
+    }
+}
+
+#[test]
+fn should_move_owned_registration_ids() {
+    let ids = vec!["one".to_string(), "two".to_string()];
+    let pointers: Vec<*const u8> = ids.iter().map(|id| id.as_ptr()).collect();
+
+    let msg = FCMRequestBuilder::to_tokens("api_key", "project", ids, None).finalize();
+    let registration_ids = msg.body.message.registration_ids.unwrap();
+
+    for (id, pointer) in registration_ids.iter().zip(pointers) {
+        assert!(matches!(id, Cow::Owned(_)));
+        assert_eq!(pointer, id.as_ptr());
+    }
+}
+
+#[test]
+fn should_borrow_registration_ids_from_a_slice() {
+    let ids = vec!["one".to_string(), "two".to_string()];
+
+    let mut builder = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    builder.registration_ids(&ids);
+    let registration_ids = builder.finalize().body.message.registration_ids.unwrap();
+
+    for (id, original) in registration_ids.iter().zip(&ids) {
+        assert!(matches!(id, Cow::Borrowed(_)));
+        assert_eq!(original.as_ptr(), id.as_ptr());
+    }
+}
+
+#[test]
+fn should_accept_registration_ids_of_any_string_type() {
+    let expected = Some(vec![Cow::from("one"), Cow::from("two")]);
+
+    let msg = FCMRequestBuilder::to_tokens("api_key", "project", &["one", "two"], None).finalize();
+    assert_eq!(expected, msg.body.message.registration_ids);
+
+    let msg = FCMRequestBuilder::to_tokens("api_key", "project", vec!["one", "two"], None).finalize();
+    assert_eq!(expected, msg.body.message.registration_ids);
+
+    let ids = vec![Cow::from("one"), Cow::from("two".to_string())];
+    let msg = FCMRequestBuilder::to_tokens("api_key", "project", ids, None).finalize();
+    assert_eq!(expected, msg.body.message.registration_ids);
+}
+
+#[test]
+fn should_fingerprint_identical_messages_the_same() {
+    let mut first = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    first.priority(Priority::High).collapse_key("foo");
+    first.data(&CustomData { foo: "bar", bar: false }).unwrap();
+
+    let mut second = FCMRequestBuilder::to_topic("other_api_key", "other-project", "token", Some(true));
+    second.collapse_key("foo").priority(Priority::High);
+    second.data(&json!({"bar": false, "foo": "bar"})).unwrap();
+
+    let first = first.finalize();
+    let second = second.finalize();
+
+    assert_eq!(first.fingerprint().unwrap(), second.fingerprint().unwrap());
+    assert_eq!(64, first.fingerprint_hex().unwrap().len());
+    assert_eq!(first.fingerprint_hex().unwrap(), second.fingerprint_hex().unwrap());
+}
+
+#[test]
+fn should_fingerprint_different_messages_differently() {
+    let mut first = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    first.data(&CustomData { foo: "bar", bar: false }).unwrap();
+
+    let mut second = FCMRequestBuilder::to_topic("api_key", "project", "token", None);
+    second.data(&CustomData { foo: "baz", bar: false }).unwrap();
+
+    assert_ne!(
+        first.finalize().fingerprint().unwrap(),
+        second.finalize().fingerprint().unwrap()
+    );
+}
+
+#[test]
+fn should_fingerprint_the_sorted_message() {
+    let msg = FCMRequestBuilder::to_topic("api_key", "project", "token", None).finalize();
+    let expected: [u8; 32] = Sha256::digest(br#"{"topic":"token"}"#).into();
+
+    assert_eq!(Ok(expected), msg.fingerprint());
+}
+
+#[test]
+fn should_serialize_the_same_bytes_whatever_the_insertion_order() {
+    let mut first = DataMapBuilder::new();
+    first.insert("b", "2").insert("a", "1").click_action("/home");
+
+    let mut second = Map::new();
+    second.insert(well_known::CLICK_ACTION.to_string(), Value::from("/home"));
+    second.insert("a".to_string(), Value::from("1"));
+    second.insert("b".to_string(), Value::from("2"));
+
+    let mut first_builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    first_builder.data_map(first.finalize()).unwrap().collapse_key("foo");
+
+    let mut second_builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    second_builder.collapse_key("foo").data_map(second).unwrap();
+
+    let first = first_builder.finalize();
+    let second = second_builder.finalize();
+
+    for _ in 0..3 {
+        assert_eq!(first.message_payload().unwrap(), second.message_payload().unwrap());
+    }
+
+    assert_eq!(
+        br#"{"android":{"collapse_key":"foo"},"data":{"a":"1","b":"2","click_action":"/home"},"token":"token"}"#
+            .to_vec(),
+        first.message_payload().unwrap()
+    );
+}
+
+#[test]
+fn should_reject_data_which_is_not_a_map() {
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+
+    assert_eq!(
+        Some(BuildError::Validation {
+            field: "data",
+            reason: "not a map".to_string()
+        }),
+        builder.data(&vec!["foo", "bar"]).err()
+    );
+}
+
+#[test]
+fn should_report_the_serialization_errors_of_the_data() {
+    let mut data = HashMap::new();
+    data.insert(vec![1, 2], "bar");
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+
+    assert!(matches!(builder.data(&data).err(), Some(BuildError::Serialization(_))));
+}
+
+/// A message with an APNs payload of exactly `size` bytes.
+fn message_with_apns_payload(size: usize, push_type: Option<&'static str>) -> FCMRequestBuilder<'static> {
+    // `{"pad":""}` is 10 bytes.
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&json!({ "pad": "x".repeat(size - 10) })).unwrap();
+
+    if let Some(push_type) = push_type {
+        apns.header("apns-push-type", push_type);
+    }
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.apns(apns.finalize());
+    builder
+}
+
+#[test]
+fn should_not_finalize_an_apns_payload_over_the_limit() {
+    for (push_type, limit) in [(None, 4096), (Some("alert"), 4096), (Some("voip"), 5120)] {
+        for size in [limit - 1, limit] {
+            let request = message_with_apns_payload(size, push_type).try_finalize().unwrap();
+            let payload = request.body.message.apns.unwrap().payload().cloned().unwrap();
+
+            assert_eq!(size, serde_json::to_vec(&payload).unwrap().len());
+        }
+
+        assert_eq!(
+            Some(BuildError::PayloadTooLarge {
+                field: "apns.payload",
+                limit,
+                size: limit + 1,
+            }),
+            message_with_apns_payload(limit + 1, push_type).try_finalize().err()
+        );
+    }
+}
+
+#[test]
+fn should_count_the_aps_flags_in_the_apns_payload_size() {
+    let mut builder = message_with_apns_payload(4096, None);
+    builder.content_available(true);
+
+    // `"aps":{"content-available":1},` is 30 bytes.
+    assert_eq!(
+        Some(BuildError::PayloadTooLarge {
+            field: "apns.payload",
+            limit: 4096,
+            size: 4126,
+        }),
+        builder.try_finalize().err()
+    );
+}
+
+/// A message with an empty collapse key, notification title and channel id.
+fn message_with_empty_strings() -> FCMRequestBuilder<'static> {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.channel_id("");
+
+    let mut android = AndroidConfigBuilder::new();
+    android.collapse_key("").notification(notification.finalize());
+
+    let mut notification = NotificationBuilder::new();
+    notification.title("").body("2-1");
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder
+        .android(android.finalize())
+        .notification(notification.finalize());
+    builder
+}
+
+#[test]
+fn should_reject_empty_strings_by_default() {
+    assert_eq!(
+        Some(BuildError::Validation {
+            field: "notification.title",
+            reason: "empty string".to_string(),
+        }),
+        message_with_empty_strings().try_finalize().err()
+    );
+
+    let mut builder = message_with_empty_strings();
+    builder.empty_strings_for("notification.title", EmptyStrings::Allow);
+
+    assert_eq!(
+        Some(BuildError::Validation {
+            field: "android.collapse_key",
+            reason: "empty string".to_string(),
+        }),
+        builder.try_finalize().err()
+    );
+
+    let mut builder = message_with_empty_strings();
+    builder
+        .empty_strings(EmptyStrings::TreatAsUnset)
+        .empty_strings_for("android.notification.channel_id", EmptyStrings::Reject);
+
+    assert_eq!(
+        Some(BuildError::Validation {
+            field: "android.notification.channel_id",
+            reason: "empty string".to_string(),
+        }),
+        builder.try_finalize().err()
+    );
+}
+
+#[test]
+fn should_unset_empty_strings() {
+    let mut builder = message_with_empty_strings();
+    builder.empty_strings(EmptyStrings::TreatAsUnset);
+
+    let payload = serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap();
+
+    assert_eq!(
+        json!({
+            "message": {
+                "android": {"notification": {}},
+                "notification": {"body": "2-1"},
+                "token": "token",
+            },
+            "validate_only": false,
+        }),
+        payload
+    );
+}
+
+#[test]
+fn should_allow_empty_strings() {
+    let mut builder = message_with_empty_strings();
+    builder.empty_strings(EmptyStrings::Allow);
+
+    let payload = serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap();
+
+    assert_eq!(
+        json!({
+            "message": {
+                "android": {"collapse_key": "", "notification": {"channel_id": ""}},
+                "notification": {"body": "2-1", "title": ""},
+                "token": "token",
+            },
+            "validate_only": false,
+        }),
+        payload
+    );
+}
+
+#[test]
+fn should_apply_the_empty_strings_policy_of_a_field() {
+    let mut builder = message_with_empty_strings();
+    builder
+        .empty_strings(EmptyStrings::TreatAsUnset)
+        .empty_strings_for("android.collapse_key", EmptyStrings::Allow);
+
+    let payload = serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap();
+
+    assert_eq!(
+        json!({"collapse_key": "", "notification": {}}),
+        payload["message"]["android"]
+    );
+}
+
+#[test]
+fn should_display_the_build_errors() {
+    for (error, display) in [
+        (
+            BuildError::Serialization("key must be a string".to_string()),
+            "cannot serialize the message: key must be a string",
+        ),
+        (
+            BuildError::Validation {
+                field: "data",
+                reason: "not a map".to_string(),
+            },
+            "invalid `data`: not a map",
+        ),
+        (
+            BuildError::Conflict {
+                fields: vec!["token", "topic"],
+            },
+            "these fields cannot be set together: token, topic",
+        ),
+        (BuildError::MissingTarget, "the message has no target"),
+        (
+            BuildError::PolicyViolation("no alert when clearing the badge".to_string()),
+            "no alert when clearing the badge",
+        ),
+        (
+            BuildError::PayloadTooLarge {
+                field: "apns.payload",
+                limit: 4096,
+                size: 4097,
+            },
+            "`apns.payload` is 4097 bytes, over the limit of 4096 bytes",
+        ),
+    ] {
+        assert_eq!(display, error.to_string());
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn should_keep_the_deprecated_error_name() {
+    let error: crate::MessageBuildError = BuildError::MissingTarget;
+
+    assert_eq!(BuildError::MissingTarget, error);
+}
+
+#[test]
+fn should_keep_the_android_data_apart_from_the_data() {
+    let mut android = AndroidConfigBuilder::new();
+    android
+        .data(&json!({"link": "app://matches/42", "score": "2-1"}))
+        .unwrap();
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder
+        .data(&json!({"link": "https://example.com/matches/42", "score": "2-1"}))
+        .unwrap()
+        .android(android.finalize());
+
+    let payload = serde_json::to_value(&builder.finalize().body).unwrap();
+
+    assert_eq!(
+        json!({"link": "https://example.com/matches/42", "score": "2-1"}),
+        payload["message"]["data"]
+    );
+    assert_eq!(
+        json!({"link": "app://matches/42", "score": "2-1"}),
+        payload["message"]["android"]["data"]
+    );
+}
+
+#[test]
+fn should_nest_a_complete_android_config_in_the_message() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.icon("ic_goal").click_action("OPEN_MATCH");
+
+    let mut android = AndroidConfigBuilder::new();
+    android
+        .collapse_key("scores")
+        .priority(Priority::High)
+        .ttl(Duration::from_secs(60))
+        .restricted_package_name("com.example.scores")
+        .data_entry("score", "2-1")
+        .unwrap()
+        .notification(notification.finalize())
+        .analytics_label("goals")
+        .direct_boot_ok(true);
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.android(android.finalize());
+
+    let payload: serde_json::Value = serde_json::from_slice(&builder.finalize().payload().unwrap()).unwrap();
+
+    assert_eq!(
+        json!({
+            "validate_only": false,
+            "message": {
+                "android": {
+                    "collapse_key": "scores",
+                    "data": {"score": "2-1"},
+                    "direct_boot_ok": true,
+                    "fcm_options": {"analytics_label": "goals"},
+                    "notification": {"click_action": "OPEN_MATCH", "icon": "ic_goal"},
+                    "priority": "HIGH",
+                    "restricted_package_name": "com.example.scores",
+                    "ttl": "60s",
+                },
+                "token": "token",
+            },
+        }),
+        payload
+    );
+}
+
+#[test]
+fn should_reject_android_colors_not_in_rrggbb_format() {
+    for color in ["#1a73e8", "#FFFFFF"] {
+        let mut notification = AndroidNotificationBuilder::new();
+        notification.color(color);
+
+        let mut android = AndroidConfigBuilder::new();
+        android.notification(notification.finalize());
+
+        let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+        builder.android(android.finalize());
+
+        assert!(builder.try_finalize().is_ok(), "{}", color);
+    }
+
+    for color in ["1a73e8", "#1a73e", "#1a73e8ff", "#1a73eg", "red"] {
+        let mut notification = AndroidNotificationBuilder::new();
+        notification.color(color);
+
+        let mut android = AndroidConfigBuilder::new();
+        android.notification(notification.finalize());
+
+        let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+        builder.android(android.finalize());
+
+        assert_eq!(
+            Some(BuildError::Validation {
+                field: "android.notification.color",
+                reason: format!("`{}` is not in #rrggbb format", color)
+            }),
+            builder.try_finalize().err()
+        );
+    }
+}
+
+#[test]
+fn should_set_the_image_only_in_the_android_notification() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.image("https://example.com/goal.png");
+
+    let mut android = AndroidConfigBuilder::new();
+    android.notification(notification.finalize());
+
+    let mut title = NotificationBuilder::new();
+    title.title("Goal!");
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.notification(title.finalize()).android(android.finalize());
+
+    let payload = serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap();
+
+    assert_eq!(
+        json!({"image": "https://example.com/goal.png"}),
+        payload["message"]["android"]["notification"]
+    );
+    assert_eq!(json!({"title": "Goal!"}), payload["message"]["notification"]);
+}
+
+#[test]
+#[cfg(feature = "url")]
+fn should_reject_android_images_not_http_urls() {
+    for image in ["https://example.com/goal.png", "http://example.com/goal.png?size=large"] {
+        let mut notification = AndroidNotificationBuilder::new();
+        notification.image(image);
+
+        let mut android = AndroidConfigBuilder::new();
+        android.notification(notification.finalize());
+
+        let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+        builder.android(android.finalize());
+
+        assert!(builder.try_finalize().is_ok(), "{}", image);
+    }
+
+    for image in [
+        "",
+        "goal.png",
+        "/images/goal.png",
+        "ftp://example.com/goal.png",
+        "data:image/png;base64,AA==",
+    ] {
+        let mut notification = AndroidNotificationBuilder::new();
+        notification.image(image);
+
+        let mut android = AndroidConfigBuilder::new();
+        android.notification(notification.finalize());
+
+        // An empty image is otherwise rejected as an empty string.
+        let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+        builder.android(android.finalize()).empty_strings(EmptyStrings::Allow);
+
+        assert_eq!(
+            Some(BuildError::Validation {
+                field: "android.notification.image",
+                reason: format!("`{}` is not an http(s) URL", image)
+            }),
+            builder.try_finalize().err()
+        );
+    }
+}
+
+#[test]
+fn should_combine_the_notification_with_an_android_channel() {
+    let mut notification = NotificationBuilder::new();
+    notification.title("Goal!").body("2-1 in the 89th minute");
+
+    let mut android_notification = AndroidNotificationBuilder::new();
+    android_notification.channel_id("scores");
+
+    let mut android = AndroidConfigBuilder::new();
+    android.notification(android_notification.finalize());
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder
+        .notification(notification.finalize())
+        .android(android.finalize());
+
+    let payload: serde_json::Value = serde_json::from_slice(&builder.finalize().message_payload().unwrap()).unwrap();
+
+    assert_eq!(
+        json!({
+            "android": {"notification": {"channel_id": "scores"}},
+            "notification": {"body": "2-1 in the 89th minute", "title": "Goal!"},
+            "token": "token",
+        }),
+        payload
+    );
+}
+
+#[test]
+fn should_build_a_silent_message_clearing_the_badge() {
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.clear_badge();
+
+    let payload: serde_json::Value =
+        serde_json::from_slice(&builder.try_finalize().unwrap().message_payload().unwrap()).unwrap();
+
+    assert_eq!(
+        json!({
+            "apns": {
+                "headers": {"apns-priority": "5", "apns-push-type": "background"},
+                "payload": {"aps": {"badge": 0, "content-available": 1}},
+            },
+            "data": {"clear_badge": "true"},
+            "token": "token",
+        }),
+        payload
+    );
+}
+
+#[test]
+fn should_keep_the_apns_payload_and_data_when_clearing_the_badge() {
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&json!({"aps": {"badge": 3, "category": "SYNC"}, "sync": "inbox"}))
+        .unwrap();
+    apns.header("APNs-Priority", "10").header("apns-collapse-id", "inbox");
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.apns(apns.finalize()).clear_badge();
+    builder.data(&json!({"inbox": "42"})).unwrap();
+
+    let payload: serde_json::Value =
+        serde_json::from_slice(&builder.try_finalize().unwrap().message_payload().unwrap()).unwrap();
+
+    assert_eq!(
+        json!({"aps": {"badge": 0, "category": "SYNC", "content-available": 1}, "sync": "inbox"}),
+        payload["apns"]["payload"]
+    );
+    assert_eq!(
+        json!({"apns-collapse-id": "inbox", "apns-priority": "5", "apns-push-type": "background"}),
+        payload["apns"]["headers"]
+    );
+    assert_eq!(json!({"clear_badge": "true", "inbox": "42"}), payload["data"]);
+}
+
+#[test]
+fn should_reject_an_alert_on_a_message_clearing_the_badge() {
+    let mut notification = NotificationBuilder::new();
+    notification.title("Hey!");
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.clear_badge().notification(notification.finalize());
+
+    assert!(matches!(
+        builder.try_finalize().err(),
+        Some(BuildError::PolicyViolation(_))
+    ));
+
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&json!({"aps": {"alert": {"title": "Hey!"}}})).unwrap();
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.clear_badge().apns(apns.finalize());
+
+    assert!(matches!(
+        builder.try_finalize().err(),
+        Some(BuildError::PolicyViolation(_))
+    ));
+}
+
+#[test]
+fn should_reject_a_custom_and_the_default_sound() {
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.sound(Sound::Custom(Cow::Owned("goal".to_string())));
+    notification.default_sound(true);
+
+    let mut android = AndroidConfigBuilder::new();
+    android.notification(notification.finalize());
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.android(android.finalize());
+
+    assert_eq!(
+        Some(BuildError::Conflict {
+            fields: vec!["android.notification.sound", "android.notification.default_sound"]
+        }),
+        builder.try_finalize().err()
+    );
+
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.sound(Sound::Custom("goal".into())).default_sound(false);
+
+    let mut android = AndroidConfigBuilder::new();
+    android.notification(notification.finalize());
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.android(android.finalize());
+
+    assert!(builder.try_finalize().is_ok());
+}
+
+fn fcm_options(analytics_label: &str) -> FcmOptions<'_> {
+    let mut fcm_options = FcmOptionsBuilder::new();
+    fcm_options.analytics_label(analytics_label);
+    fcm_options.finalize()
+}
+
+#[test]
+fn should_serialize_the_fcm_options_of_each_level_independently() {
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder.fcm_options(fcm_options("all"));
+
+    assert_eq!(
+        json!({"fcm_options": {"analytics_label": "all"}, "token": "token"}),
+        serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap()["message"]
+    );
+
+    let mut android = AndroidConfigBuilder::new();
+    android.fcm_options(fcm_options("android"));
+
+    let mut apns = ApnsConfigBuilder::new();
+    apns.fcm_options(fcm_options("apns"));
+
+    let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+    builder
+        .fcm_options(fcm_options("all"))
+        .android(android.finalize())
+        .apns(apns.finalize());
+
+    assert_eq!(
+        json!({
+            "android": {"fcm_options": {"analytics_label": "android"}},
+            "apns": {"fcm_options": {"analytics_label": "apns"}},
+            "fcm_options": {"analytics_label": "all"},
+            "token": "token",
+        }),
+        serde_json::to_value(&builder.try_finalize().unwrap().body).unwrap()["message"]
+    );
+}
+
+#[test]
+fn should_reject_invalid_analytics_labels() {
+    let too_long = "a".repeat(51);
+
+    for label in ["campaign-42", "Summer_2024.v1~%20", &"a".repeat(50)] {
+        let mut builder = FCMRequestBuilder::to_token("api_key", "project", "token", None);
+        builder.fcm_options(fcm_options(label));
+
+        assert!(builder.try_finalize().is_ok(), "{}", label);
+    }
+
+    for label in ["", too_long.as_str(), "summer sale", "été", "campaign/42"] {
+        let mut android = AndroidConfigBuilder::new();
+        android.analytics_label(label);
+
+        let mut apns = ApnsConfigBuilder::new();
+        apns.fcm_options(fcm_options(label));
+
+        let mut builders = [
+            FCMRequestBuilder::to_token("api_key", "project", "token", None),
+            FCMRequestBuilder::to_token("api_key", "project", "token", None),
+            FCMRequestBuilder::to_token("api_key", "project", "token", None),
+        ];
+        builders[0].fcm_options(fcm_options(label));
+        builders[1].android(android.finalize());
+        builders[2].apns(apns.finalize());
+
+        let fields = [
+            "fcm_options.analytics_label",
+            "android.fcm_options.analytics_label",
+            "apns.fcm_options.analytics_label",
+        ];
+
+        for (mut builder, field) in builders.into_iter().zip(fields) {
+            // An empty label is otherwise rejected as an empty string.
+            builder.empty_strings(EmptyStrings::Allow);
+
+            assert_eq!(
+                Some(BuildError::Validation {
+                    field,
+                    reason: format!("`{}` is not 1 to 50 characters among [a-zA-Z0-9-_.~%]", label)
+                }),
+                builder.try_finalize().err()
+            );
+        }
+    }
 }
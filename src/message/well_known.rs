@@ -0,0 +1,22 @@
+//! Data keys interpreted by the Firebase client SDKs, and the keys FCM
+//! reserves for itself.
+
+/// The action run when the user taps the notification. The Flutter plugin
+/// expects it to be `FLUTTER_NOTIFICATION_CLICK`.
+pub const CLICK_ACTION: &str = "click_action";
+
+/// The value of `CLICK_ACTION` the Flutter plugin reacts to.
+pub const FLUTTER_NOTIFICATION_CLICK: &str = "FLUTTER_NOTIFICATION_CLICK";
+
+/// The Android notification channel the app should display the message in.
+pub const ANDROID_CHANNEL_ID: &str = "android_channel_id";
+
+/// Set to `true` by `FCMRequestBuilder::clear_badge`, for the app to clear
+/// its badge when it receives the message on Android.
+pub const CLEAR_BADGE: &str = "clear_badge";
+
+/// The data keys FCM rejects: `from`, `message_type`, and any key starting
+/// with `google` or `gcm`. None of the constants of this module is reserved.
+pub fn is_reserved(key: &str) -> bool {
+    key == "from" || key == "message_type" || key.starts_with("google") || key.starts_with("gcm")
+}
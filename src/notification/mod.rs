@@ -1,4 +1,5 @@
 use serde::Serialize;
+#[cfg(feature = "legacy-http")]
 use std::borrow::Cow;
 
 #[cfg(test)]
@@ -7,54 +8,210 @@ mod tests;
 /// This struct represents a FCM notification. Use the
 /// corresponding `NotificationBuilder` to get an instance. You can then use
 /// this notification instance when sending a FCM message.
-#[derive(Serialize, Debug, PartialEq)]
+///
+/// The v1 API only accepts the title and the body of the notification. The
+/// other fields only exist in the legacy HTTP API, with the `legacy-http`
+/// feature: in the v1 API they are set for each platform instead, e.g. with
+/// `AndroidNotificationBuilder` and `ApnsConfigBuilder::payload`.
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct Notification<'a> {
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     badge: Option<&'a str>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     body: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     body_loc_args: Option<Vec<Cow<'a, str>>>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     body_loc_key: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     click_action: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     color: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     icon: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     sound: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     tag: Option<&'a str>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     title: Option<&'a str>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     title_loc_args: Option<Vec<Cow<'a, str>>>,
 
+    #[cfg(feature = "legacy-http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     title_loc_key: Option<&'a str>,
 }
 
+impl<'a> Notification<'a> {
+    /// The title of the notification
+    pub fn title(&self) -> Option<&'a str> {
+        self.title
+    }
+
+    /// The body of the notification
+    pub fn body(&self) -> Option<&'a str> {
+        self.body
+    }
+
+    /// The notification icon
+    #[cfg(feature = "legacy-http")]
+    pub fn icon(&self) -> Option<&'a str> {
+        self.icon
+    }
+
+    /// The sound to be played
+    #[cfg(feature = "legacy-http")]
+    pub fn sound(&self) -> Option<&'a str> {
+        self.sound
+    }
+
+    /// The badge for iOS notifications
+    #[cfg(feature = "legacy-http")]
+    pub fn badge(&self) -> Option<&'a str> {
+        self.badge
+    }
+
+    /// The tag used to replace existing notifications
+    #[cfg(feature = "legacy-http")]
+    pub fn tag(&self) -> Option<&'a str> {
+        self.tag
+    }
+
+    /// The color of the icon, in #rrggbb format
+    #[cfg(feature = "legacy-http")]
+    pub fn color(&self) -> Option<&'a str> {
+        self.color
+    }
+
+    /// What happens when the user clicks on the notification
+    #[cfg(feature = "legacy-http")]
+    pub fn click_action(&self) -> Option<&'a str> {
+        self.click_action
+    }
+
+    /// The body key string for localization
+    #[cfg(feature = "legacy-http")]
+    pub fn body_loc_key(&self) -> Option<&'a str> {
+        self.body_loc_key
+    }
+
+    /// String values replacing format specifiers in the body string
+    #[cfg(feature = "legacy-http")]
+    pub fn body_loc_args(&self) -> Option<&[Cow<'a, str>]> {
+        self.body_loc_args.as_deref()
+    }
+
+    /// The title key string for localization
+    #[cfg(feature = "legacy-http")]
+    pub fn title_loc_key(&self) -> Option<&'a str> {
+        self.title_loc_key
+    }
+
+    /// String values replacing format specifiers in the title string
+    #[cfg(feature = "legacy-http")]
+    pub fn title_loc_args(&self) -> Option<&[Cow<'a, str>]> {
+        self.title_loc_args.as_deref()
+    }
+
+    /// The string fields `FCMRequestBuilder::empty_strings` applies to, by
+    /// path in the message.
+    pub(crate) fn string_fields(&mut self) -> Vec<(&'static str, &mut Option<&'a str>)> {
+        #[allow(unused_mut)]
+        let mut fields = vec![
+            ("notification.body", &mut self.body),
+            ("notification.title", &mut self.title),
+        ];
+
+        #[cfg(feature = "legacy-http")]
+        fields.extend([
+            ("notification.badge", &mut self.badge),
+            ("notification.body_loc_key", &mut self.body_loc_key),
+            ("notification.click_action", &mut self.click_action),
+            ("notification.color", &mut self.color),
+            ("notification.icon", &mut self.icon),
+            ("notification.sound", &mut self.sound),
+            ("notification.tag", &mut self.tag),
+            ("notification.title_loc_key", &mut self.title_loc_key),
+        ]);
+
+        fields
+    }
+
+    /// Convert the notification back into a `NotificationBuilder`, so it can
+    /// be modified and finalized again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fcm_http1::NotificationBuilder;
+    ///
+    /// let mut builder = NotificationBuilder::new();
+    /// builder.title("Australia vs New Zealand");
+    /// let notification = builder.finalize();
+    ///
+    /// let mut builder = notification.to_builder();
+    /// builder.body("3 runs to win in 1 ball");
+    /// let notification = builder.finalize();
+    ///
+    /// assert_eq!(notification.title(), Some("Australia vs New Zealand"));
+    /// ```
+    pub fn to_builder(&self) -> NotificationBuilder<'a> {
+        NotificationBuilder {
+            title: self.title,
+            body: self.body,
+            #[cfg(feature = "legacy-http")]
+            icon: self.icon,
+            #[cfg(feature = "legacy-http")]
+            sound: self.sound,
+            #[cfg(feature = "legacy-http")]
+            badge: self.badge,
+            #[cfg(feature = "legacy-http")]
+            tag: self.tag,
+            #[cfg(feature = "legacy-http")]
+            color: self.color,
+            #[cfg(feature = "legacy-http")]
+            click_action: self.click_action,
+            #[cfg(feature = "legacy-http")]
+            body_loc_key: self.body_loc_key,
+            #[cfg(feature = "legacy-http")]
+            body_loc_args: self.body_loc_args.clone(),
+            #[cfg(feature = "legacy-http")]
+            title_loc_key: self.title_loc_key,
+            #[cfg(feature = "legacy-http")]
+            title_loc_args: self.title_loc_args.clone(),
+        }
+    }
+}
+
 /// A builder to get a `Notification` instance.
 ///
 /// # Examples
 ///
 /// ```rust
-/// use fcm::NotificationBuilder;
+/// use fcm_http1::NotificationBuilder;
 ///
 /// let mut builder = NotificationBuilder::new();
-//  builder.title("Australia vs New Zealand");
+/// builder.title("Australia vs New Zealand");
 /// builder.body("3 runs to win in 1 ball");
 /// let notification = builder.finalize();
 /// ```
@@ -62,15 +219,25 @@ pub struct Notification<'a> {
 pub struct NotificationBuilder<'a> {
     title: Option<&'a str>,
     body: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     icon: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     sound: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     badge: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     tag: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     color: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     click_action: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     body_loc_key: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     body_loc_args: Option<Vec<Cow<'a, str>>>,
+    #[cfg(feature = "legacy-http")]
     title_loc_key: Option<&'a str>,
+    #[cfg(feature = "legacy-http")]
     title_loc_args: Option<Vec<Cow<'a, str>>>,
 }
 
@@ -93,31 +260,41 @@ impl<'a> NotificationBuilder<'a> {
     }
 
     /// Set the notification icon.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn icon(&mut self, icon: &'a str) -> &mut Self {
         self.icon = Some(icon);
         self
     }
 
-    /// Set the sound to be played
+    /// Set the sound to be played.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn sound(&mut self, sound: &'a str) -> &mut Self {
         self.sound = Some(sound);
         self
     }
 
-    /// Set the badge for iOS notifications
+    /// Set the badge for iOS notifications.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn badge(&mut self, badge: &'a str) -> &mut Self {
         self.badge = Some(badge);
         self
     }
 
     /// Tagging a notification allows you to replace existing notifications
-    /// with the same tag with this new notification
+    /// with the same tag with this new notification.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn tag(&mut self, tag: &'a str) -> &mut Self {
         self.tag = Some(tag);
         self
     }
 
-    /// The color of the icon, in #rrggbb format
+    /// The color of the icon, in #rrggbb format.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn color(&mut self, color: &'a str) -> &mut Self {
         self.color = Some(color);
         self
@@ -126,18 +303,24 @@ impl<'a> NotificationBuilder<'a> {
     /// What happens when the user clicks on the notification. Refer to
     /// https://developers.google.com/cloud-messaging/http-server-ref#table2 for
     /// details.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn click_action(&mut self, click_action: &'a str) -> &mut Self {
         self.click_action = Some(click_action);
         self
     }
 
-    /// Set the body key string for localization
+    /// Set the body key string for localization.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn body_loc_key(&mut self, body_loc_key: &'a str) -> &mut Self {
         self.body_loc_key = Some(body_loc_key);
         self
     }
 
     /// String value to replace format specifiers in the body string.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn body_loc_args<S>(&mut self, body_loc_args: &'a [S]) -> &mut Self
     where
         S: Into<Cow<'a, str>> + AsRef<str>,
@@ -148,13 +331,17 @@ impl<'a> NotificationBuilder<'a> {
         self
     }
 
-    /// Set the title key string for localization
+    /// Set the title key string for localization.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn title_loc_key(&mut self, title_loc_key: &'a str) -> &mut Self {
         self.title_loc_key = Some(title_loc_key);
         self
     }
 
     /// String value to replace format specifiers in the title string.
+    /// Only supported by the legacy HTTP API.
+    #[cfg(feature = "legacy-http")]
     pub fn title_loc_args<S>(&mut self, title_loc_args: &'a [S]) -> &mut Self
     where
         S: Into<Cow<'a, str>> + AsRef<str>,
@@ -170,15 +357,25 @@ impl<'a> NotificationBuilder<'a> {
         Notification {
             title: self.title,
             body: self.body,
+            #[cfg(feature = "legacy-http")]
             icon: self.icon,
+            #[cfg(feature = "legacy-http")]
             sound: self.sound,
+            #[cfg(feature = "legacy-http")]
             badge: self.badge,
+            #[cfg(feature = "legacy-http")]
             tag: self.tag,
+            #[cfg(feature = "legacy-http")]
             color: self.color,
+            #[cfg(feature = "legacy-http")]
             click_action: self.click_action,
+            #[cfg(feature = "legacy-http")]
             body_loc_key: self.body_loc_key,
+            #[cfg(feature = "legacy-http")]
             body_loc_args: self.body_loc_args,
+            #[cfg(feature = "legacy-http")]
             title_loc_key: self.title_loc_key,
+            #[cfg(feature = "legacy-http")]
             title_loc_args: self.title_loc_args,
         }
     }
@@ -1,8 +1,10 @@
 use crate::NotificationBuilder;
 use serde_json::json;
+#[cfg(feature = "legacy-http")]
 use std::borrow::Cow;
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_be_able_to_render_a_full_notification_to_json() {
     let mut builder = NotificationBuilder::new();
 
@@ -41,6 +43,17 @@ fn should_be_able_to_render_a_full_notification_to_json() {
     assert_eq!(expected_payload, payload);
 }
 
+#[test]
+fn should_only_render_the_v1_fields_of_a_notification() {
+    let mut builder = NotificationBuilder::new();
+    builder.title("foo").body("bar");
+
+    assert_eq!(
+        json!({"body": "bar", "title": "foo"}),
+        serde_json::to_value(builder.finalize()).unwrap()
+    );
+}
+
 #[test]
 fn should_set_notification_title() {
     let nm = NotificationBuilder::new().finalize();
@@ -68,6 +81,7 @@ fn should_set_notification_body() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_icon() {
     let mut builder = NotificationBuilder::new();
     builder.icon("newicon");
@@ -77,6 +91,7 @@ fn should_set_notification_icon() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_sound() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -90,6 +105,7 @@ fn should_set_notification_sound() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_badge() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -103,6 +119,7 @@ fn should_set_notification_badge() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_tag() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -116,6 +133,7 @@ fn should_set_notification_tag() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_color() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -129,6 +147,7 @@ fn should_set_notification_color() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_click_action() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -142,6 +161,7 @@ fn should_set_notification_click_action() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_body_loc_key() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -155,6 +175,7 @@ fn should_set_notification_body_loc_key() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_body_loc_args() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -168,6 +189,7 @@ fn should_set_notification_body_loc_args() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_title_loc_key() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -181,6 +203,7 @@ fn should_set_notification_title_loc_key() {
 }
 
 #[test]
+#[cfg(feature = "legacy-http")]
 fn should_set_notification_title_loc_args() {
     let nm = NotificationBuilder::new().finalize();
 
@@ -192,3 +215,57 @@ fn should_set_notification_title_loc_args() {
 
     assert_eq!(nm.title_loc_args, Some(vec![Cow::from("args")]));
 }
+
+#[test]
+fn should_expose_notification_fields() {
+    let mut builder = NotificationBuilder::new();
+    builder.title("title").body("body");
+    #[cfg(feature = "legacy-http")]
+    builder.body_loc_args(&["args"]);
+    let nm = builder.finalize();
+
+    assert_eq!(nm.title(), Some("title"));
+    assert_eq!(nm.body(), Some("body"));
+    #[cfg(feature = "legacy-http")]
+    {
+        assert_eq!(nm.body_loc_args(), Some(&[Cow::from("args")][..]));
+        assert_eq!(nm.icon(), None);
+    }
+}
+
+#[test]
+fn should_round_trip_a_notification_through_its_builder() {
+    let mut builder = NotificationBuilder::new();
+
+    builder.title("foo").body("bar");
+    #[cfg(feature = "legacy-http")]
+    builder
+        .icon("gif")
+        .sound("pling")
+        .badge("12")
+        .tag("spook")
+        .color("#666666")
+        .click_action("spam")
+        .body_loc_key("PLAY")
+        .body_loc_args(&["foo", "bar"])
+        .title_loc_key("PAUSE")
+        .title_loc_args(&["omg", "lol"]);
+
+    let nm = builder.finalize();
+
+    assert_eq!(nm, nm.to_builder().finalize());
+}
+
+#[test]
+fn should_modify_a_notification_through_its_builder() {
+    let mut builder = NotificationBuilder::new();
+    builder.title("title");
+    let nm = builder.finalize();
+
+    let mut builder = nm.to_builder();
+    builder.body("body");
+    let nm = builder.finalize();
+
+    assert_eq!(nm.title(), Some("title"));
+    assert_eq!(nm.body(), Some("body"));
+}
@@ -0,0 +1,202 @@
+//! Previews of what each platform shows for a message, for internal tools.
+//!
+//! The previews are resolved from the payload sent to FCM, with the override
+//! rules of FCM: the Android and APNs specific options take precedence over
+//! the notification of the message, and `android.data` replaces the data of
+//! the message on Android. The notification of the message only has a sound
+//! and a badge with the `legacy-http` feature.
+
+use crate::message::{well_known, FCMRequest};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod tests;
+
+/// What each platform shows for a message, from `render`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlatformPreviews {
+    pub android: AndroidPreview,
+    pub ios: IosPreview,
+    pub web: WebPreview,
+}
+
+/// How a message changes the badge of the app.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BadgeEffect {
+    Unchanged,
+    Set(u32),
+    Clear,
+}
+
+/// What an Android device shows for a message.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AndroidPreview {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub image: Option<String>,
+
+    /// The sound played, `default` for the default sound of the device.
+    pub sound: Option<String>,
+
+    /// Whether nothing is shown, the data being handed to the app.
+    pub silent: bool,
+
+    pub badge: BadgeEffect,
+    pub channel: Option<String>,
+
+    /// The data the app receives.
+    pub data: BTreeMap<String, String>,
+}
+
+/// What an Apple device shows for a message.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IosPreview {
+    pub title: Option<String>,
+    pub body: Option<String>,
+
+    /// The sound played, `default` for the default sound of the device.
+    pub sound: Option<String>,
+
+    /// Whether nothing is shown, e.g. for a background notification.
+    pub silent: bool,
+
+    pub badge: BadgeEffect,
+
+    /// The data the app receives.
+    pub data: BTreeMap<String, String>,
+}
+
+/// What a browser shows for a message.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WebPreview {
+    pub title: Option<String>,
+    pub body: Option<String>,
+
+    /// Whether nothing is shown, the data being handed to the app.
+    pub silent: bool,
+
+    /// The data the app receives.
+    pub data: BTreeMap<String, String>,
+}
+
+/// Resolve what each platform shows for `request`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fcm_http1::{preview, FCMRequestBuilder, NotificationBuilder};
+///
+/// let mut notification = NotificationBuilder::new();
+/// notification.title("Goal!");
+///
+/// let mut builder = FCMRequestBuilder::to_token("<FCM API Key>", "<project>", "<registration token>", None);
+/// builder.notification(notification.finalize());
+///
+/// let previews = preview::render(&builder.finalize());
+/// assert_eq!(Some("Goal!"), previews.android.title.as_deref());
+/// ```
+pub fn render(request: &FCMRequest<'_>) -> PlatformPreviews {
+    let message: Value = serde_json::from_slice(&request.message_payload().unwrap()).unwrap();
+
+    PlatformPreviews {
+        android: android(&message),
+        ios: ios(&message),
+        web: web(&message),
+    }
+}
+
+fn android(message: &Value) -> AndroidPreview {
+    let notification = &message["notification"];
+    let android = &message["android"]["notification"];
+
+    let sound = string(&android["sound"])
+        .or_else(|| (android["default_sound"] == true).then(|| "default".to_string()))
+        .or_else(|| string(&notification["sound"]));
+
+    let data = match message["android"]["data"] {
+        Value::Object(_) => &message["android"]["data"],
+        _ => &message["data"],
+    };
+
+    // Without a notification count, the app clears its badge on the flag of
+    // `FCMRequestBuilder::clear_badge`, if it receives it.
+    let badge = match android["notification_count"].as_i64() {
+        Some(0) => BadgeEffect::Clear,
+        Some(count) => u32::try_from(count).map_or(BadgeEffect::Unchanged, BadgeEffect::Set),
+        None if data[well_known::CLEAR_BADGE] == "true" => BadgeEffect::Clear,
+        None => BadgeEffect::Unchanged,
+    };
+
+    AndroidPreview {
+        title: string(&notification["title"]),
+        body: string(&notification["body"]),
+        image: string(&android["image"]),
+        sound,
+        silent: notification.is_null() && android.is_null(),
+        badge,
+        channel: string(&android["channel_id"]),
+        data: string_map(data),
+    }
+}
+
+fn ios(message: &Value) -> IosPreview {
+    let notification = &message["notification"];
+    let aps = &message["apns"]["payload"]["aps"];
+
+    let (title, body) = match aps["alert"] {
+        Value::String(ref body) => (None, Some(body.clone())),
+        ref alert => (string(&alert["title"]), string(&alert["body"])),
+    };
+
+    // A critical alert names its sound in a dictionary.
+    let sound = string(&aps["sound"])
+        .or_else(|| string(&aps["sound"]["name"]))
+        .or_else(|| string(&notification["sound"]));
+
+    let badge = aps["badge"]
+        .as_u64()
+        .or_else(|| notification["badge"].as_str().and_then(|badge| badge.parse().ok()))
+        .and_then(|badge| u32::try_from(badge).ok());
+
+    IosPreview {
+        title: title.or_else(|| string(&notification["title"])),
+        body: body.or_else(|| string(&notification["body"])),
+        sound,
+        silent: notification.is_null() && aps["alert"].is_null(),
+        badge: match badge {
+            Some(0) => BadgeEffect::Clear,
+            Some(badge) => BadgeEffect::Set(badge),
+            None => BadgeEffect::Unchanged,
+        },
+        data: string_map(&message["data"]),
+    }
+}
+
+fn web(message: &Value) -> WebPreview {
+    let notification = &message["notification"];
+
+    WebPreview {
+        title: string(&notification["title"]),
+        body: string(&notification["body"]),
+        silent: notification.is_null(),
+        data: string_map(&message["data"]),
+    }
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// The data map as the apps receive it, the values which are not strings as
+/// their JSON text.
+fn string_map(data: &Value) -> BTreeMap<String, String> {
+    data.as_object()
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| match value {
+            Value::String(value) => (key.clone(), value.clone()),
+            value => (key.clone(), value.to_string()),
+        })
+        .collect()
+}
@@ -0,0 +1,456 @@
+use crate::preview::{render, AndroidPreview, BadgeEffect, IosPreview, WebPreview};
+use crate::{AndroidConfigBuilder, AndroidNotificationBuilder, ApnsConfigBuilder, FCMRequest, FCMRequestBuilder};
+use crate::{NotificationBuilder, Sound};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+fn builder() -> FCMRequestBuilder<'static> {
+    FCMRequestBuilder::to_token("api_key", "project", "token", None)
+}
+
+fn goal_notification(builder: &mut FCMRequestBuilder<'static>) {
+    let mut notification = NotificationBuilder::new();
+    notification.title("Goal!").body("2-1");
+    builder.notification(notification.finalize());
+}
+
+fn android_notification(builder: &mut FCMRequestBuilder<'static>, notification: AndroidNotificationBuilder<'static>) {
+    let mut android = AndroidConfigBuilder::new();
+    android.notification(notification.finalize());
+    builder.android(android.finalize());
+}
+
+fn apns_payload(builder: &mut FCMRequestBuilder<'static>, payload: serde_json::Value) {
+    let mut apns = ApnsConfigBuilder::new();
+    apns.payload(&payload).unwrap();
+    builder.apns(apns.finalize());
+}
+
+fn data(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn android_silent() -> AndroidPreview {
+    AndroidPreview {
+        title: None,
+        body: None,
+        image: None,
+        sound: None,
+        silent: true,
+        badge: BadgeEffect::Unchanged,
+        channel: None,
+        data: BTreeMap::new(),
+    }
+}
+
+fn android_goal() -> AndroidPreview {
+    AndroidPreview {
+        title: Some("Goal!".to_string()),
+        body: Some("2-1".to_string()),
+        silent: false,
+        ..android_silent()
+    }
+}
+
+fn ios_silent() -> IosPreview {
+    IosPreview {
+        title: None,
+        body: None,
+        sound: None,
+        silent: true,
+        badge: BadgeEffect::Unchanged,
+        data: BTreeMap::new(),
+    }
+}
+
+fn ios_goal() -> IosPreview {
+    IosPreview {
+        title: Some("Goal!".to_string()),
+        body: Some("2-1".to_string()),
+        silent: false,
+        ..ios_silent()
+    }
+}
+
+#[test]
+fn should_preview_android() {
+    let cases: Vec<(&str, FCMRequest<'static>, AndroidPreview)> = vec![
+        ("data only message", builder().finalize(), android_silent()),
+        (
+            "notification of the message",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                builder.finalize()
+            },
+            android_goal(),
+        ),
+        (
+            "android notification alone",
+            {
+                let mut builder = builder();
+                let mut notification = AndroidNotificationBuilder::new();
+                notification.channel_id("scores").image("https://example.com/goal.png");
+                android_notification(&mut builder, notification);
+                builder.finalize()
+            },
+            AndroidPreview {
+                image: Some("https://example.com/goal.png".to_string()),
+                channel: Some("scores".to_string()),
+                silent: false,
+                ..android_silent()
+            },
+        ),
+        (
+            "android sound",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                let mut notification = AndroidNotificationBuilder::new();
+                notification.sound(Sound::Custom("goal".into()));
+                android_notification(&mut builder, notification);
+                builder.finalize()
+            },
+            AndroidPreview {
+                sound: Some("goal".to_string()),
+                ..android_goal()
+            },
+        ),
+        (
+            "android default sound",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                let mut notification = AndroidNotificationBuilder::new();
+                notification.sound(Sound::Default);
+                android_notification(&mut builder, notification);
+                builder.finalize()
+            },
+            AndroidPreview {
+                sound: Some("default".to_string()),
+                ..android_goal()
+            },
+        ),
+        (
+            "notification count",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                let mut notification = AndroidNotificationBuilder::new();
+                notification.notification_count(5);
+                android_notification(&mut builder, notification);
+                builder.finalize()
+            },
+            AndroidPreview {
+                badge: BadgeEffect::Set(5),
+                ..android_goal()
+            },
+        ),
+        (
+            "zero notification count",
+            {
+                let mut builder = builder();
+                let mut notification = AndroidNotificationBuilder::new();
+                notification.notification_count(0);
+                android_notification(&mut builder, notification);
+                builder.finalize()
+            },
+            AndroidPreview {
+                badge: BadgeEffect::Clear,
+                silent: false,
+                ..android_silent()
+            },
+        ),
+        (
+            "badge clearing",
+            {
+                let mut builder = builder();
+                builder.clear_badge();
+                builder.finalize()
+            },
+            AndroidPreview {
+                badge: BadgeEffect::Clear,
+                data: data(&[("clear_badge", "true")]),
+                ..android_silent()
+            },
+        ),
+        (
+            "badge clearing replaced by the android data",
+            {
+                let mut builder = builder();
+                builder.clear_badge();
+                let mut android = AndroidConfigBuilder::new();
+                android.data_entry("link", "app://matches/42").unwrap();
+                builder.android(android.finalize());
+                builder.finalize()
+            },
+            AndroidPreview {
+                data: data(&[("link", "app://matches/42")]),
+                ..android_silent()
+            },
+        ),
+        (
+            "data of the message",
+            {
+                let mut builder = builder();
+                builder
+                    .data(&json!({"link": "https://example.com", "score": 2}))
+                    .unwrap();
+                builder.finalize()
+            },
+            AndroidPreview {
+                data: data(&[("link", "https://example.com"), ("score", "2")]),
+                ..android_silent()
+            },
+        ),
+        (
+            "android data replacing the data of the message",
+            {
+                let mut builder = builder();
+                builder
+                    .data(&json!({"link": "https://example.com", "score": "2"}))
+                    .unwrap();
+                let mut android = AndroidConfigBuilder::new();
+                android.data_entry("link", "app://matches/42").unwrap();
+                builder.android(android.finalize());
+                builder.finalize()
+            },
+            AndroidPreview {
+                data: data(&[("link", "app://matches/42")]),
+                ..android_silent()
+            },
+        ),
+    ];
+
+    for (name, request, expected) in cases {
+        assert_eq!(expected, render(&request).android, "{}", name);
+    }
+}
+
+#[test]
+fn should_preview_ios() {
+    let cases: Vec<(&str, FCMRequest<'static>, IosPreview)> = vec![
+        ("data only message", builder().finalize(), ios_silent()),
+        (
+            "notification of the message",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                builder.finalize()
+            },
+            ios_goal(),
+        ),
+        (
+            "alert string as the body",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                apns_payload(&mut builder, json!({"aps": {"alert": "3-1"}}));
+                builder.finalize()
+            },
+            IosPreview {
+                body: Some("3-1".to_string()),
+                ..ios_goal()
+            },
+        ),
+        (
+            "alert dictionary over the notification",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                apns_payload(&mut builder, json!({"aps": {"alert": {"title": "Own goal!"}}}));
+                builder.finalize()
+            },
+            IosPreview {
+                title: Some("Own goal!".to_string()),
+                ..ios_goal()
+            },
+        ),
+        (
+            "alert without notification",
+            {
+                let mut builder = builder();
+                apns_payload(
+                    &mut builder,
+                    json!({"aps": {"alert": {"title": "Kick-off", "body": "0-0"}}}),
+                );
+                builder.finalize()
+            },
+            IosPreview {
+                title: Some("Kick-off".to_string()),
+                body: Some("0-0".to_string()),
+                silent: false,
+                ..ios_silent()
+            },
+        ),
+        (
+            "aps sound and badge",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                apns_payload(&mut builder, json!({"aps": {"sound": "goal.caf", "badge": 7}}));
+                builder.finalize()
+            },
+            IosPreview {
+                sound: Some("goal.caf".to_string()),
+                badge: BadgeEffect::Set(7),
+                ..ios_goal()
+            },
+        ),
+        (
+            "critical alert sound",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                apns_payload(
+                    &mut builder,
+                    json!({"aps": {"sound": {"critical": 1, "name": "siren.caf"}}}),
+                );
+                builder.finalize()
+            },
+            IosPreview {
+                sound: Some("siren.caf".to_string()),
+                ..ios_goal()
+            },
+        ),
+        (
+            "background notification",
+            {
+                let mut builder = builder();
+                builder.content_available(true);
+                builder.finalize()
+            },
+            ios_silent(),
+        ),
+        (
+            "badge clearing",
+            {
+                let mut builder = builder();
+                builder.clear_badge();
+                builder.finalize()
+            },
+            IosPreview {
+                badge: BadgeEffect::Clear,
+                data: data(&[("clear_badge", "true")]),
+                ..ios_silent()
+            },
+        ),
+        (
+            "android data not applying",
+            {
+                let mut builder = builder();
+                builder.data(&json!({"link": "https://example.com"})).unwrap();
+                let mut android = AndroidConfigBuilder::new();
+                android.data_entry("link", "app://matches/42").unwrap();
+                builder.android(android.finalize());
+                builder.finalize()
+            },
+            IosPreview {
+                data: data(&[("link", "https://example.com")]),
+                ..ios_silent()
+            },
+        ),
+    ];
+
+    for (name, request, expected) in cases {
+        assert_eq!(expected, render(&request).ios, "{}", name);
+    }
+}
+
+#[test]
+#[cfg(feature = "legacy-http")]
+fn should_preview_the_sound_and_badge_of_a_legacy_notification() {
+    let legacy_notification = |builder: &mut FCMRequestBuilder<'static>| {
+        let mut notification = NotificationBuilder::new();
+        notification.title("Goal!").body("2-1").sound("whistle").badge("3");
+        builder.notification(notification.finalize());
+    };
+
+    let mut request = builder();
+    legacy_notification(&mut request);
+    let previews = render(&request.finalize());
+
+    assert_eq!(
+        AndroidPreview {
+            sound: Some("whistle".to_string()),
+            ..android_goal()
+        },
+        previews.android
+    );
+    assert_eq!(
+        IosPreview {
+            sound: Some("whistle".to_string()),
+            badge: BadgeEffect::Set(3),
+            ..ios_goal()
+        },
+        previews.ios
+    );
+
+    let mut request = builder();
+    legacy_notification(&mut request);
+    let mut notification = AndroidNotificationBuilder::new();
+    notification.sound(Sound::Custom("goal".into()));
+    android_notification(&mut request, notification);
+    apns_payload(&mut request, json!({"aps": {"sound": "goal.caf", "badge": 7}}));
+    let previews = render(&request.finalize());
+
+    assert_eq!(Some("goal"), previews.android.sound.as_deref());
+    assert_eq!(Some("goal.caf"), previews.ios.sound.as_deref());
+    assert_eq!(BadgeEffect::Set(7), previews.ios.badge);
+}
+
+#[test]
+fn should_preview_web() {
+    let cases: Vec<(&str, FCMRequest<'static>, WebPreview)> = vec![
+        (
+            "data only message",
+            {
+                let mut builder = builder();
+                builder.data(&json!({"score": "2-1"})).unwrap();
+                builder.finalize()
+            },
+            WebPreview {
+                title: None,
+                body: None,
+                silent: true,
+                data: data(&[("score", "2-1")]),
+            },
+        ),
+        (
+            "notification of the message, the platform options not applying",
+            {
+                let mut builder = builder();
+                goal_notification(&mut builder);
+                apns_payload(&mut builder, json!({"aps": {"alert": {"title": "Own goal!"}}}));
+                builder.finalize()
+            },
+            WebPreview {
+                title: Some("Goal!".to_string()),
+                body: Some("2-1".to_string()),
+                silent: false,
+                data: BTreeMap::new(),
+            },
+        ),
+    ];
+
+    for (name, request, expected) in cases {
+        assert_eq!(expected, render(&request).web, "{}", name);
+    }
+}
+
+#[test]
+fn should_preview_the_pruned_payload() {
+    let mut builder = builder();
+    builder
+        .notification(NotificationBuilder::new().finalize())
+        .prune_empty(true);
+
+    let previews = render(&builder.finalize());
+
+    assert!(previews.android.silent);
+    assert!(previews.ios.silent);
+    assert!(previews.web.silent);
+}
@@ -0,0 +1,149 @@
+//! Serde helpers for the protobuf JSON mapping of the `Duration` and
+//! `Timestamp` well-known types, to be used with `#[serde(serialize_with)]`
+//! and `#[serde(deserialize_with)]`.
+//!
+//! Durations are written as seconds with an `s` suffix, timestamps as RFC 3339
+//! UTC dates with a `Z` suffix. The fractional seconds of both are written
+//! with 0, 3, 6 or 9 digits, whichever is the shortest exact one, except by
+//! the `_nanos` helpers which always write 9 digits.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::Serializer;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// The longest duration protobuf accepts, about 10,000 years.
+const MAX_DURATION_SECONDS: u64 = 315_576_000_000;
+
+/// Format a duration as a protobuf duration string, e.g. `3600s` or `1.500s`.
+pub fn format_duration(duration: Duration) -> String {
+    let (secs, nanos) = (duration.as_secs(), duration.subsec_nanos());
+
+    if nanos == 0 {
+        format!("{}s", secs)
+    } else if nanos % 1_000_000 == 0 {
+        format!("{}.{:03}s", secs, nanos / 1_000_000)
+    } else if nanos % 1_000 == 0 {
+        format!("{}.{:06}s", secs, nanos / 1_000)
+    } else {
+        format!("{}.{:09}s", secs, nanos)
+    }
+}
+
+/// Parse a protobuf duration string, with up to 9 fractional digits. Negative
+/// durations are not supported.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.strip_suffix('s')?;
+    let (secs, fraction) = s.split_once('.').unwrap_or((s, ""));
+
+    if secs.is_empty() || fraction.len() > 9 || !(secs.bytes().chain(fraction.bytes())).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let secs: u64 = secs.parse().ok().filter(|secs| *secs <= MAX_DURATION_SECONDS)?;
+    let nanos = fraction
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(9)
+        .fold(0, |nanos, digit| nanos * 10 + u32::from(digit - b'0'));
+
+    Some(Duration::new(secs, nanos))
+}
+
+/// Format a timestamp as a protobuf timestamp string, e.g.
+/// `2017-01-15T01:30:15.010Z`.
+pub fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Format a timestamp as a protobuf timestamp string with 9 fractional
+/// digits, e.g. `2017-01-15T01:30:15.010000000Z`.
+pub fn format_timestamp_nanos(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+/// Parse a protobuf timestamp string. Offsets other than `Z` are accepted
+/// and converted to UTC.
+pub fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+/// Serialize a duration as a protobuf duration string.
+pub fn serialize_duration<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_duration(*duration))
+}
+
+/// Deserialize a protobuf duration string.
+pub fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_duration(&s).ok_or_else(|| D::Error::custom(format!("invalid duration {:?}", s)))
+}
+
+/// Serialize a timestamp as a protobuf timestamp string.
+pub fn serialize_timestamp<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_timestamp(timestamp))
+}
+
+/// Serialize a timestamp as a protobuf timestamp string with 9 fractional
+/// digits.
+pub fn serialize_timestamp_nanos<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_timestamp_nanos(timestamp))
+}
+
+/// Deserialize a protobuf timestamp string.
+pub fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_timestamp(&s).ok_or_else(|| D::Error::custom(format!("invalid timestamp {:?}", s)))
+}
+
+/// Same as `serialize_duration`, for the optional fields skipped when unset.
+pub(crate) fn serialize_optional_duration<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialize_duration(&duration.unwrap_or_default(), serializer)
+}
+
+/// Same as `serialize_timestamp_nanos`, for the optional fields skipped when
+/// unset.
+pub(crate) fn serialize_optional_timestamp_nanos<S>(
+    timestamp: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialize_timestamp_nanos(&timestamp.unwrap_or_default(), serializer)
+}
+
+/// Serialize a list of durations as protobuf duration strings, for the
+/// optional fields skipped when unset.
+pub(crate) fn serialize_optional_durations<S>(
+    durations: &Option<Vec<Duration>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(durations.iter().flatten().map(|duration| format_duration(*duration)))
+}
@@ -0,0 +1,125 @@
+use crate::wire_time::{
+    deserialize_duration, deserialize_timestamp, format_duration, format_timestamp, format_timestamp_nanos,
+    parse_duration, parse_timestamp, serialize_duration, serialize_timestamp,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Times {
+    #[serde(serialize_with = "serialize_duration", deserialize_with = "deserialize_duration")]
+    duration: Duration,
+
+    #[serde(serialize_with = "serialize_timestamp", deserialize_with = "deserialize_timestamp")]
+    timestamp: DateTime<Utc>,
+}
+
+#[test]
+fn should_format_durations_with_3_6_or_9_fractional_digits() {
+    for (duration, expected) in [
+        (Duration::ZERO, "0s"),
+        (Duration::from_secs(1), "1s"),
+        (Duration::from_secs(3600), "3600s"),
+        (Duration::from_millis(1010), "1.010s"),
+        (Duration::from_millis(1500), "1.500s"),
+        (Duration::from_micros(1), "0.000001s"),
+        (Duration::from_micros(1_000_340), "1.000340s"),
+        (Duration::new(1, 340_012), "1.000340012s"),
+        (Duration::new(0, 1), "0.000000001s"),
+    ] {
+        assert_eq!(expected, format_duration(duration));
+        assert_eq!(Some(duration), parse_duration(expected));
+    }
+}
+
+#[test]
+fn should_parse_durations_with_any_number_of_fractional_digits() {
+    assert_eq!(Some(Duration::from_millis(1500)), parse_duration("1.5s"));
+    assert_eq!(Some(Duration::from_millis(1500)), parse_duration("1.50s"));
+    assert_eq!(
+        Some(Duration::from_secs(315_576_000_000)),
+        parse_duration("315576000000s")
+    );
+}
+
+#[test]
+fn should_not_parse_invalid_durations() {
+    for duration in [
+        "",
+        "s",
+        "1",
+        "1.s5",
+        "-1s",
+        "+1s",
+        ".5s",
+        "1.0000000001s",
+        "1e3s",
+        "1 s",
+        "315576000001s",
+    ] {
+        assert_eq!(None, parse_duration(duration), "{:?}", duration);
+    }
+}
+
+#[test]
+fn should_format_timestamps_in_utc_with_3_6_or_9_fractional_digits() {
+    let timestamp: DateTime<Utc> = "1972-01-01T10:00:20Z".parse().unwrap();
+
+    for (nanos, expected) in [
+        (0, "1972-01-01T10:00:20Z"),
+        (21_000_000, "1972-01-01T10:00:20.021Z"),
+        (21_000, "1972-01-01T10:00:20.000021Z"),
+        (21, "1972-01-01T10:00:20.000000021Z"),
+    ] {
+        let timestamp = timestamp + chrono::Duration::nanoseconds(nanos);
+
+        assert_eq!(expected, format_timestamp(&timestamp));
+        assert_eq!(Some(timestamp), parse_timestamp(expected));
+    }
+}
+
+#[test]
+fn should_format_timestamps_with_9_fractional_digits() {
+    let timestamp: DateTime<Utc> = "1972-01-01T10:00:20Z".parse().unwrap();
+
+    for (nanos, expected) in [
+        (0, "1972-01-01T10:00:20.000000000Z"),
+        (21_000_000, "1972-01-01T10:00:20.021000000Z"),
+        (21, "1972-01-01T10:00:20.000000021Z"),
+    ] {
+        let timestamp = timestamp + chrono::Duration::nanoseconds(nanos);
+
+        assert_eq!(expected, format_timestamp_nanos(&timestamp));
+        assert_eq!(Some(timestamp), parse_timestamp(expected));
+    }
+}
+
+#[test]
+fn should_parse_timestamps_with_offsets() {
+    assert_eq!(
+        parse_timestamp("1972-01-01T15:00:20.021Z"),
+        parse_timestamp("1972-01-01T10:00:20.021-05:00")
+    );
+
+    assert_eq!(None, parse_timestamp("1972-01-01 10:00:20"));
+}
+
+#[test]
+fn should_round_trip_through_serde() {
+    let times = Times {
+        duration: Duration::from_millis(1500),
+        timestamp: "2017-01-15T01:30:15.010Z".parse().unwrap(),
+    };
+
+    let value = serde_json::to_value(&times).unwrap();
+
+    assert_eq!(
+        json!({"duration": "1.500s", "timestamp": "2017-01-15T01:30:15.010Z"}),
+        value
+    );
+    assert_eq!(times, serde_json::from_value(value).unwrap());
+
+    assert!(serde_json::from_value::<Times>(json!({"duration": "1.5", "timestamp": "2017-01-15T01:30:15Z"})).is_err());
+}